@@ -1,105 +1,91 @@
-use bevy::{
-    asset::RenderAssetUsages, ecs::world::DeferredWorld, prelude::*,
-    render::render_resource::TextureFormat,
-};
+use bevy::{asset::RenderAssetUsages, pbr::MaterialPlugin, prelude::*, render::primitives::Aabb};
 
-use crate::{board::spawner::DimensionSpawner, pieces::Team};
+use crate::game::{InGame, NewGameSet};
+use crate::pieces::{Captured, ChessPiece, Team};
 
-mod spawner;
+mod axes;
+mod layout;
+mod material;
+mod mesh;
+mod picking;
+
+pub use axes::{OnCell, RenderedAxes, SliceViewport, SplitView};
+pub use layout::{DimensionLayout, LayoutKind, Perspective4D};
+pub use material::{BoardTheme, CellMaterial};
+pub use picking::HoveredCell;
 
 pub struct BoardPlugin;
 
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<BoardResource>()
+        app.add_plugins(MaterialPlugin::<CellMaterial>::default())
+            .init_resource::<BoardTheme>()
+            .init_resource::<BoardResource>()
             .init_resource::<BoardState>()
             .insert_resource(Dimensions(5))
-            .add_systems(Startup, spawn_board)
-            .register_type::<Index>()
+            .init_resource::<RenderedAxes>()
+            .add_plugins(picking::CellPickingPlugin)
+            .add_systems(OnEnter(InGame), spawn_board.in_set(NewGameSet::Spawn))
+            .add_systems(
+                Update,
+                (
+                    (
+                        axes::slice_controls,
+                        axes::rotate_4d,
+                        material::theme_controls,
+                    ),
+                    (
+                        rebuild_board,
+                        axes::relayout_positions,
+                        axes::assign_slice_layers,
+                        material::apply_theme,
+                    ),
+                )
+                    .chain()
+                    .run_if(in_state(InGame)),
+            )
             .register_type::<Dimensions>()
-            .register_type::<DimensionSpawner>();
+            .register_type::<RenderedAxes>()
+            .register_type::<BoardTheme>();
         app.add_systems(Last, captured_piece);
     }
 }
 
 #[derive(Resource)]
 struct BoardResource {
-    cube_handle: Handle<Mesh>,
-    black_material: Handle<StandardMaterial>,
-    white_material: Handle<StandardMaterial>,
+    cell_handle: Handle<Mesh>,
+    dark_material: Handle<CellMaterial>,
+    light_material: Handle<CellMaterial>,
 }
 
 impl FromWorld for BoardResource {
     fn from_world(world: &mut World) -> Self {
-        // let cube_handle = world
-        //     .resource_mut::<Assets<Mesh>>()
-        //     .add(Mesh::from(Cuboid::from_size(Vec3::new(1.0, 0.01, 8.0))));
-        let cube_handle = world
-            .resource_mut::<Assets<Mesh>>()
-            .add(get_rectangle_mesh());
-        let mut images = world.resource_mut::<Assets<Image>>();
-        let pixel = 1.0f32.to_be_bytes().repeat(4);
-        let mut w_image = Image::new_fill(
-            bevy::render::render_resource::Extent3d {
-                width: 1,
-                height: 8,
-                depth_or_array_layers: 1,
-            },
-            bevy::render::render_resource::TextureDimension::D2,
-            &pixel,
-            TextureFormat::Rgba32Float,
-            bevy::asset::RenderAssetUsages::all(),
-        );
-        let mut b_image = Image::new_fill(
-            bevy::render::render_resource::Extent3d {
-                width: 1,
-                height: 8,
-                depth_or_array_layers: 1,
-            },
-            bevy::render::render_resource::TextureDimension::D2,
-            &pixel,
-            TextureFormat::Rgba32Float,
-            bevy::asset::RenderAssetUsages::all(),
-        );
-        for i in (0..8).step_by(2) {
-            let _ = b_image.set_color_at(0, i, Color::WHITE);
-            let _ = w_image.set_color_at(0, i + 1, Color::WHITE);
-        }
-        let w_handle = images.add(w_image);
-        let b_handle = images.add(b_image);
-        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
-        let black_material = materials.add(StandardMaterial {
-            base_color_texture: Some(b_handle),
-            ..Default::default()
-        });
-        let white_material = materials.add(StandardMaterial {
-            base_color_texture: Some(w_handle),
-            ..Default::default()
-        });
+        let cell_handle = world.resource_mut::<Assets<Mesh>>().add(get_cell_mesh());
+        let theme = world.get_resource_or_init::<BoardTheme>().clone();
+        let mut materials = world.resource_mut::<Assets<CellMaterial>>();
+        let dark_material = materials.add(theme.dark_material());
+        let light_material = materials.add(theme.light_material());
 
         BoardResource {
-            cube_handle,
-            black_material,
-            white_material,
+            cell_handle,
+            dark_material,
+            light_material,
         }
     }
 }
 
-fn get_rectangle_mesh() -> Mesh {
+/// A single cell, drawn from both sides so the board can be seen from below
+fn get_cell_mesh() -> Mesh {
     let mut mesh = Mesh::new(
         bevy::render::mesh::PrimitiveTopology::TriangleList,
         RenderAssetUsages::all(),
     );
-    // ([max.x, max.y, min.z], [0.0, 1.0, 0.0], [1.0, 0.0]),
-    // ([min.x, max.y, min.z], [0.0, 1.0, 0.0], [0.0, 0.0]),
-    // ([min.x, max.y, max.z], [0.0, 1.0, 0.0], [0.0, 1.0]),
-    // ([max.x, max.y, max.z], [0.0, 1.0, 0.0], [1.0, 1.0]),
     mesh.insert_attribute(
         Mesh::ATTRIBUTE_POSITION,
         vec![
             // Front
-            [0.5, 0.0, -7.5],
-            [-0.5, 0.0, -7.5],
+            [0.5, 0.0, -0.5],
+            [-0.5, 0.0, -0.5],
             [-0.5, 0.0, 0.5],
             [0.5, 0.0, 0.5],
         ],
@@ -121,25 +107,10 @@ fn get_rectangle_mesh() -> Mesh {
     mesh
 }
 
-#[derive(Component, Deref, Reflect)]
-struct Index(i8);
-
 #[derive(Deref, Clone, Component, Hash, PartialEq, Eq, Default, Debug)]
 pub struct Position(pub Vec<i8>);
 
 impl Position {
-    fn new(world: &DeferredWorld, cell: Entity) -> Self {
-        let mut out = Position(vec![0]);
-        let mut next = cell;
-        while let Some(parent) = world.get::<ChildOf>(next) {
-            let Some(index) = world.get::<Index>(next) else {
-                break;
-            };
-            out.0.push(index.0);
-            next = parent.parent();
-        }
-        out
-    }
     fn sum(&self) -> usize {
         self.0.iter().map(|&x| x as usize).sum()
     }
@@ -180,20 +151,27 @@ impl Position {
         self.0.last().copied().unwrap_or(8)
     }
 
-    pub fn to_translation(&self) -> Vec3 {
-        let mut pos = Vec3::ZERO;
-        for (d, i) in self.iter().enumerate() {
-            if let Some(step) = spawner::render_dimension_step_size(d + 1) {
-                pos += step * (*i as f32);
-            }
-        }
-        pos
+    pub fn to_translation(&self, axes: &RenderedAxes) -> Vec3 {
+        axes.layout
+            .layout()
+            .translation(&axes.spatial_position(self), axes.size)
     }
 
-    /// currently only dimensions < 7 are visible
-    /// in future will take in a map of rendered dimensions and return true if the piece would be visible with the currently rendered dimensions
-    pub fn is_visible(&self, dimensions: usize) -> bool {
-        dimensions < 7
+    /// How large things on this cell are drawn, some layouts shrink cells that are further away in higher dimensions
+    pub fn render_scale(&self, axes: &RenderedAxes) -> f32 {
+        axes.layout
+            .layout()
+            .scale(&axes.spatial_position(self), axes.size)
+    }
+
+    /// true if every axis that is not laid out in space is on the slice being shown,
+    /// or on the slice of one of the viewports for the split axis
+    pub fn is_visible(&self, axes: &RenderedAxes) -> bool {
+        let split = axes.split.as_ref().map(|split| split.axis);
+        axes.hidden()
+            .filter(|axis| Some(*axis) != split)
+            .all(|axis| self.get(axis) == axes.slice.get(axis))
+            && axes.viewport(self).is_some()
     }
 
     pub fn all_but(&self, dim: usize, val: i8) -> bool {
@@ -233,9 +211,10 @@ impl Position {
         }
         out
     }
-    pub fn is_valid(&self) -> bool {
+    /// On a board with `size` cells along every axis
+    pub fn is_valid(&self, size: i8) -> bool {
         for &i in self.0.iter() {
-            if !(0..=7).contains(&i) {
+            if !(0..size).contains(&i) {
                 return false;
             }
         }
@@ -253,6 +232,59 @@ impl Position {
     }
 }
 
+/// Written like algebraic notation, the file is axis 1 as a letter and the rank is axis 0.
+/// Every further axis follows counting from 1, so `e2.1.4` is on index 0 of axis 2 and 3 of axis 3.
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(&file) = self.get(1) {
+            match u8::try_from(file).ok().filter(|file| *file < 26) {
+                Some(file) => write!(f, "{}", (b'a' + file) as char)?,
+                // off the board, still written so it shows up in the message about it
+                None => write!(f, "({file})")?,
+            }
+        }
+        if let Some(&rank) = self.first() {
+            write!(f, "{}", i16::from(rank) + 1)?;
+        }
+        for &index in self.iter().skip(2) {
+            write!(f, ".{}", i16::from(index) + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// The notation `Display` writes, `e2.1.4`
+impl std::str::FromStr for Position {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut chars = text.chars();
+        let file = chars
+            .next()
+            .filter(char::is_ascii_lowercase)
+            .ok_or_else(|| format!("{text} doesn't start with a file letter"))?;
+        let mut position = vec![0, file as i8 - b'a' as i8];
+        for (i, index) in chars.as_str().split('.').enumerate() {
+            // parsed wider then a cell so counting from 1 can't overflow
+            let index = index
+                .parse::<i16>()
+                .map_err(|error| format!("{text} has a bad index {index:?}: {error}"))?;
+            let index = i8::try_from(index - 1).map_err(|_| format!("{text} is off the board"))?;
+            if i == 0 {
+                position[0] = index;
+            } else {
+                position.push(index);
+            }
+        }
+        let position = Position(position);
+        // the game being played checks the cell is on its board, this only turns away cells no board has
+        if !position.is_valid(MAX_BOARD_SIZE as i8) {
+            return Err(format!("{text} is off the board"));
+        }
+        Ok(position)
+    }
+}
+
 impl core::ops::Add for Position {
     type Output = Position;
 
@@ -383,12 +415,13 @@ impl<const DISTANCE: u8> Iterator for NewPositionIter<DISTANCE> {
 
 pub struct OffsetIter<'a, T: Iterator<Item = Position>> {
     base: &'a Position,
+    size: i8,
     iter: T,
 }
 
 impl<'a, T: Iterator<Item = Position>> OffsetIter<'a, T> {
-    pub fn new(base: &'a Position, iter: T) -> Self {
-        Self { base, iter }
+    pub fn new(base: &'a Position, size: i8, iter: T) -> Self {
+        Self { base, size, iter }
     }
 }
 
@@ -400,7 +433,7 @@ impl<T: Iterator<Item = Position>> Iterator for OffsetIter<'_, T> {
             p.add(self.base);
             p
         })?;
-        if out.is_valid() {
+        if out.is_valid(self.size) {
             Some(out)
         } else {
             self.next()
@@ -408,48 +441,184 @@ impl<T: Iterator<Item = Position>> Iterator for OffsetIter<'_, T> {
     }
 }
 
-fn spawn_board(mut commands: Commands, dimensions: Res<Dimensions>) {
-    commands.spawn((
-        Name::new("Board Root"),
-        Transform::default(),
-        DimensionSpawner::new(**dimensions),
+/// Parent of the board meshes, cell picking reports its hits on this
+#[derive(Component)]
+pub struct BoardRoot;
+
+/// The board is drawn as one merged mesh per material rather then an entity per cell.
+/// The split view gets a board per viewport on that viewport's render layer.
+fn spawn_board(
+    mut commands: Commands,
+    axes: Res<RenderedAxes>,
+    resource: Res<BoardResource>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for viewport in (0..axes.viewports()).map(SliceViewport) {
+        let shown = axes.for_viewport(viewport.0);
+        let layers = axes
+            .split
+            .as_ref()
+            .map(|_| viewport.layer())
+            .unwrap_or_default();
+        let batches = mesh::build_board_meshes(&shown, &resource, &meshes);
+        commands
+            .spawn((
+                Name::new("Board Root"),
+                BoardRoot,
+                StateScoped(InGame),
+                viewport,
+                Transform::default(),
+            ))
+            .with_children(|root| {
+                for (mesh, material) in batches {
+                    root.spawn((
+                        Name::new("Board Mesh"),
+                        Mesh3d(meshes.add(mesh)),
+                        MeshMaterial3d(material),
+                        layers.clone(),
+                        // cells are found with `cell_at_ray`, raycasting every triangle is far too slow
+                        Pickable::IGNORE,
+                    ));
+                }
+            });
+    }
+}
+
+/// The board mesh only has the shown cells where the layout puts them,
+/// so it is rebuilt when the spatial axes, the slice, the kind of layout or the split view change.
+/// Rotating a layout keeps the same cells, only their vertices are moved.
+fn rebuild_board(
+    mut commands: Commands,
+    axes: Res<RenderedAxes>,
+    resource: Res<BoardResource>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    roots: Query<(Entity, &SliceViewport, &Children), With<BoardRoot>>,
+    board_meshes: Query<(&Mesh3d, &MeshMaterial3d<CellMaterial>)>,
+    mut shown: Local<Option<(Vec<usize>, Vec<i8>, LayoutKind, Option<SplitView>)>>,
+) {
+    let Some((spatial, slice, layout, split)) = shown.as_mut() else {
+        *shown = Some((
+            axes.spatial.clone(),
+            axes.slice.clone(),
+            axes.layout,
+            axes.split.clone(),
+        ));
+        return;
+    };
+    let same_cells = *spatial == axes.spatial
+        && *slice == axes.slice
+        && layout.same_kind(&axes.layout)
+        && *split == axes.split;
+    if same_cells {
+        if *layout != axes.layout {
+            *layout = axes.layout;
+            move_board_cells(
+                &mut commands,
+                &axes,
+                &resource,
+                &mut meshes,
+                &roots,
+                &board_meshes,
+            );
+        }
+        return;
+    }
+    *shown = Some((
+        axes.spatial.clone(),
+        axes.slice.clone(),
+        axes.layout,
+        axes.split.clone(),
     ));
+    for (root, ..) in &roots {
+        commands.entity(root).despawn();
+    }
+    spawn_board(commands, axes, resource, meshes);
+}
+
+/// Put the vertices of the board meshes where the layout now draws their cells,
+/// the meshes are built in the same order every time so only the positions change
+fn move_board_cells(
+    commands: &mut Commands,
+    axes: &RenderedAxes,
+    resource: &BoardResource,
+    meshes: &mut Assets<Mesh>,
+    roots: &Query<(Entity, &SliceViewport, &Children), With<BoardRoot>>,
+    board_meshes: &Query<(&Mesh3d, &MeshMaterial3d<CellMaterial>)>,
+) {
+    for (_, viewport, children) in roots {
+        let batches = mesh::build_board_meshes(&axes.for_viewport(viewport.0), resource, meshes);
+        for child in children.iter() {
+            let Ok((mesh, material)) = board_meshes.get(child) else {
+                continue;
+            };
+            let moved = batches
+                .iter()
+                .find(|(_, batch_material)| *batch_material == material.0)
+                .and_then(|(batch, _)| batch.attribute(Mesh::ATTRIBUTE_POSITION));
+            if let (Some(moved), Some(mesh)) = (moved, meshes.get_mut(&mesh.0)) {
+                mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, moved.clone());
+                // the bounds are worked out again for the moved cells, or they could be culled
+                commands.entity(child).remove::<Aabb>();
+            }
+        }
+    }
 }
 
 pub trait WithOffset {
-    fn with_offset(self, offset: &Position) -> OffsetIter<Self>
+    /// Move every position by `offset`, leaving out the ones off a board of `size`
+    fn with_offset(self, offset: &Position, size: i8) -> OffsetIter<Self>
     where
         Self: Sized + Iterator<Item = Position>,
     {
-        OffsetIter::new(offset, self)
+        OffsetIter::new(offset, size, self)
     }
 }
 
 impl<T: Iterator<Item = Position>> WithOffset for T {}
 
+/// Cells along every axis until a game picks its own size
+pub const DEFAULT_BOARD_SIZE: usize = 8;
+pub const MIN_BOARD_SIZE: usize = 5;
+pub const MAX_BOARD_SIZE: usize = 10;
+/// The furthest a piece can slide on the largest board
+pub const MAX_REACH: i8 = MAX_BOARD_SIZE as i8 - 1;
+
 #[derive(Resource, Deref, DerefMut, Reflect)]
-pub struct Dimensions(usize);
+pub struct Dimensions(pub usize);
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct BoardState {
+    /// cells along every axis
+    size: i8,
     captured: Option<Entity>,
     board: bevy::platform::collections::HashMap<Position, Entity>,
 }
 
 impl FromWorld for BoardState {
     fn from_world(_world: &mut World) -> Self {
-        Self::new()
+        Self::new(DEFAULT_BOARD_SIZE)
     }
 }
 
 impl BoardState {
-    pub fn new() -> Self {
+    /// An empty board with `size` cells along every axis
+    pub fn new(size: usize) -> Self {
         Self {
+            size: size.clamp(MIN_BOARD_SIZE, MAX_BOARD_SIZE) as i8,
             captured: None,
             board: bevy::platform::collections::HashMap::default(),
         }
     }
 
+    pub fn size(&self) -> i8 {
+        self.size
+    }
+
+    /// Whether `position` is a cell on this board
+    pub fn contains(&self, position: &Position) -> bool {
+        position.is_valid(self.size)
+    }
+
     pub fn get(&self, position: &Position) -> Option<Entity> {
         self.board.get(position).copied()
     }
@@ -458,15 +627,88 @@ impl BoardState {
         self.board.insert(position, team);
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (&Position, Entity)> {
+        self.board
+            .iter()
+            .map(|(position, entity)| (position, *entity))
+    }
+
     pub fn move_piece(&mut self, from: &Position, to: &Position) {
         if let Some(team) = self.board.remove(from) {
             self.captured = self.board.insert(to.clone(), team);
         }
     }
+
+    /// Take every piece off, the size stays the same
+    pub fn clear(&mut self) {
+        self.captured = None;
+        self.board.clear();
+    }
+
+    /// The piece the last move took, if it took one
+    pub fn take_captured(&mut self) -> Option<Entity> {
+        self.captured.take()
+    }
 }
 
 fn captured_piece(mut state: ResMut<BoardState>, mut commands: Commands) {
-    if let Some(captured) = state.captured.take() {
-        commands.entity(captured).despawn();
+    // runs every frame, the move that took the piece already marked the board as changed
+    if let Some(captured) = state.bypass_change_detection().take_captured() {
+        // no longer a piece so it can't be selected while the capture animation plays
+        commands
+            .entity(captured)
+            .remove::<ChessPiece>()
+            .insert(Captured::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_round_trips() {
+        for cells in [vec![1, 4], vec![0, 0, 0], vec![7, 7, 3, 0, 7]] {
+            let position = Position(cells);
+            assert_eq!(position.to_string().parse(), Ok(position));
+        }
+        assert_eq!("e2.1.4".parse(), Ok(Position(vec![1, 4, 0, 3])));
+    }
+
+    #[test]
+    fn malformed_positions_are_errors() {
+        for text in [
+            "", "e", "E2", "2e", "e0", "e11", "k1", "e2.", "e2.x", "e2.0", "e-128", "e2.-128",
+            "e128", "e99999",
+        ] {
+            assert!(text.parse::<Position>().is_err(), "{text:?} parsed");
+        }
+    }
+
+    #[test]
+    fn boards_only_hold_cells_of_their_size() {
+        let corner = "j10".parse::<Position>().unwrap();
+        assert!(BoardState::new(10).contains(&corner));
+        assert!(!BoardState::new(8).contains(&corner));
+        assert!(BoardState::new(5).contains(&"e5".parse().unwrap()));
+        assert!(!BoardState::new(5).contains(&"e6".parse().unwrap()));
+    }
+
+    #[test]
+    fn positions_off_the_board_are_written() {
+        assert_eq!(Position(vec![-1, 127]).to_string(), "(127)0");
+        assert_eq!(Position(vec![127, -128, 127]).to_string(), "(-128)128.128");
+    }
+
+    #[test]
+    fn splitting_along_a_spatial_axis_puts_it_back() {
+        let whole = RenderedAxes::all(3, 6);
+        let mut axes = whole.clone();
+        axes.split(2);
+        assert_eq!(axes.spatial, vec![0, 1]);
+        axes.split(4);
+        assert_eq!(axes.viewports(), 4);
+        axes.split(1);
+        assert_eq!(axes, whole);
     }
 }
@@ -0,0 +1,417 @@
+use bevy::{prelude::*, render::view::RenderLayers};
+
+use crate::board::{DEFAULT_BOARD_SIZE, Dimensions, LayoutKind, Perspective4D, Position};
+use crate::pieces::MoveTween;
+
+/// The most cells laid out at once, as many as 7 axes of 8 cells
+pub const MAX_NESTED_CELLS: usize = 8usize.pow(7);
+pub const PAGED_NESTED_AXES: usize = 5;
+
+/// Which axes are laid out in space and which slice is shown of every other axis
+#[derive(Resource, Clone, Debug, PartialEq, Reflect)]
+pub struct RenderedAxes {
+    /// axes in the order they are nested, the first is the strip of cells
+    pub spatial: Vec<usize>,
+    /// the index shown for each axis, only used for axes that are not spatial
+    pub slice: Vec<i8>,
+    /// the hidden axis the slice controls step through
+    pub stepping: usize,
+    /// where the spatial axes are placed in the world
+    pub layout: LayoutKind,
+    /// when set the window is split and each viewport shows its own slice
+    pub split: Option<SplitView>,
+    /// cells along every axis of the board being shown
+    pub size: i8,
+}
+
+/// Several viewports side by side, each showing a different slice of one hidden axis
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct SplitView {
+    pub axis: usize,
+    /// the slice each viewport shows, in viewport order
+    pub slices: Vec<i8>,
+    /// the spatial axis taken out to split along, put back when the split ends
+    pub taken: Option<usize>,
+}
+
+/// Which viewport of the split view a camera or board root belongs to, 0 is the main camera
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SliceViewport(pub usize);
+
+impl SliceViewport {
+    /// Viewport 0 draws render layer 1 and so on, layer 0 is drawn in all of them
+    pub fn layer(&self) -> RenderLayers {
+        RenderLayers::layer(self.0 + 1)
+    }
+}
+
+/// A marker drawn on a cell that places itself rather then being placed by `Position`,
+/// it is only drawn in the viewport showing that cell like anything with a `Position`
+#[derive(Component, Clone, Debug)]
+pub struct OnCell(pub Position);
+
+impl RenderedAxes {
+    /// Lay out every axis, past `MAX_NESTED_CELLS` there are too many cells to spawn
+    /// so only `PAGED_NESTED_AXES` are laid out and the rest are paged through as slices
+    pub fn all(dimensions: usize, size: i8) -> Self {
+        let cells = (size as usize).checked_pow(dimensions as u32);
+        if cells.is_some_and(|cells| cells <= MAX_NESTED_CELLS) {
+            Self::sliced(dimensions, dimensions, size)
+        } else {
+            Self::sliced(dimensions, PAGED_NESTED_AXES, size)
+        }
+    }
+
+    /// Only lay out the first `shown` axes, every other axis is fixed at slice 0
+    pub fn sliced(dimensions: usize, shown: usize, size: i8) -> Self {
+        let spatial = (0..shown.min(dimensions)).collect::<Vec<_>>();
+        Self {
+            stepping: spatial.len().min(dimensions.saturating_sub(1)),
+            spatial,
+            slice: vec![0; dimensions],
+            layout: LayoutKind::default(),
+            split: None,
+            size,
+        }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.slice.len()
+    }
+
+    pub fn is_spatial(&self, axis: usize) -> bool {
+        self.spatial.contains(&axis)
+    }
+
+    pub fn hidden(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.dimensions()).filter(|axis| !self.is_spatial(*axis))
+    }
+
+    /// Fill in a position found by walking the board tree, which only has spatial axes, with the current slice
+    pub fn full_position(&self, spatial: &Position) -> Position {
+        let mut out = Position(self.slice.clone());
+        for (slot, &axis) in self.spatial.iter().enumerate() {
+            if let Some(&index) = spatial.get(slot) {
+                out.0[axis] = index;
+            }
+        }
+        out
+    }
+
+    /// The index of `position` on each spatial axis, in the order they are laid out
+    pub fn spatial_position(&self, position: &Position) -> Vec<i8> {
+        self.spatial
+            .iter()
+            .map(|&axis| position.get(axis).copied().unwrap_or(0))
+            .collect()
+    }
+
+    /// Every index on the spatial axes from `from` onwards, the slots before it are left at 0
+    pub fn spatial_cells(&self, from: usize) -> impl Iterator<Item = Vec<i8>> + '_ {
+        let shown = self.spatial.len().saturating_sub(from);
+        let size = self.size as usize;
+        (0..size.pow(shown as u32)).map(move |mut cell| {
+            let mut spatial = vec![0; self.spatial.len()];
+            for index in spatial.iter_mut().skip(from) {
+                *index = (cell % size) as i8;
+                cell /= size;
+            }
+            spatial
+        })
+    }
+
+    /// Every 2D board shown, as its index on the spatial axes past the first 2 with those 2 left at 0
+    pub fn boards(&self) -> impl Iterator<Item = Vec<i8>> + '_ {
+        self.spatial_cells(2)
+    }
+
+    /// Every cell of `board`, one of `boards`
+    pub fn board_cells<'a>(&self, board: &'a [i8]) -> impl Iterator<Item = Vec<i8>> + 'a {
+        on_board(board, (0..self.size).collect())
+    }
+
+    /// The cells at the corners of `board`, every layout draws the rest of the board inside the box around them
+    pub fn board_corners<'a>(&self, board: &'a [i8]) -> impl Iterator<Item = Vec<i8>> + 'a {
+        on_board(board, vec![0, self.size - 1])
+    }
+
+    /// The world space box around every shown cell and the pieces on them,
+    /// or only the 2D board holding `board` when given
+    pub fn bounds(&self, board: Option<&Position>) -> (Vec3, Vec3) {
+        let layout = self.layout.layout();
+        let board = board.map(|board| self.spatial_position(board));
+        let mut min = Vec3::INFINITY;
+        let mut max = Vec3::NEG_INFINITY;
+        for shown in self.boards() {
+            if let Some(board) = &board
+                && shown.iter().zip(board).skip(2).any(|(a, b)| a != b)
+            {
+                continue;
+            }
+            for spatial in self.board_corners(&shown) {
+                let center = layout.translation(&spatial, self.size);
+                let scale = layout.scale(&spatial, self.size);
+                min = min.min(center - Vec3::new(0.5, 0., 0.5) * scale);
+                max = max.max(center + Vec3::new(0.5, 1., 0.5) * scale);
+            }
+        }
+        (min, max)
+    }
+
+    /// How many viewports the window is split into
+    pub fn viewports(&self) -> usize {
+        self.split.as_ref().map_or(1, |split| split.slices.len())
+    }
+
+    /// The viewport showing `position`, `None` if its slice isn't in any of them
+    pub fn viewport(&self, position: &Position) -> Option<usize> {
+        let Some(split) = &self.split else {
+            return Some(0);
+        };
+        let index = position.get(split.axis)?;
+        split.slices.iter().position(|slice| slice == index)
+    }
+
+    /// The axes a single viewport of the split view shows
+    pub fn for_viewport(&self, viewport: usize) -> RenderedAxes {
+        let mut axes = self.clone();
+        if let Some(split) = axes.split.take()
+            && let Some(&slice) = split.slices.get(viewport)
+        {
+            axes.slice[split.axis] = slice;
+        }
+        axes
+    }
+
+    /// Split the window into `viewports` each showing the next slice of the stepping axis,
+    /// if every axis is spatial the outermost one is taken out to split along until the window is whole again
+    pub fn split(&mut self, viewports: usize) {
+        let mut taken = self.split.take().and_then(|split| split.taken);
+        if viewports < 2 {
+            if let Some(axis) = taken {
+                self.spatial.push(axis);
+            }
+            return;
+        }
+        if self.is_spatial(self.stepping) {
+            let Some(outer) = self.spatial.pop() else {
+                return;
+            };
+            self.stepping = outer;
+            taken = Some(outer);
+        }
+        let first = self.slice[self.stepping];
+        self.split = Some(SplitView {
+            axis: self.stepping,
+            slices: (0..viewports as i8)
+                .map(|i| (first + i).rem_euclid(self.size))
+                .collect(),
+            taken,
+        });
+    }
+
+    /// Move the slice of the `stepping` axis, wrapping around the board
+    pub fn step_slice(&mut self, by: i8) {
+        if self.is_spatial(self.stepping) {
+            return;
+        }
+        if let Some(split) = &mut self.split
+            && split.axis == self.stepping
+        {
+            // page every viewport along together
+            for slice in split.slices.iter_mut() {
+                *slice = (*slice + by).rem_euclid(self.size);
+            }
+        }
+        if let Some(index) = self.slice.get_mut(self.stepping) {
+            *index = (*index + by).rem_euclid(self.size);
+        }
+    }
+
+    /// Pick the next hidden axis for the slice controls
+    pub fn cycle_stepping(&mut self) {
+        let hidden = self.hidden().collect::<Vec<_>>();
+        if hidden.is_empty() {
+            return;
+        }
+        let next = hidden
+            .iter()
+            .position(|&axis| axis == self.stepping)
+            .map_or(0, |i| (i + 1) % hidden.len());
+        self.stepping = hidden[next];
+    }
+
+    /// Swap the stepping axis with the outermost spatial axis
+    pub fn swap_stepping(&mut self) {
+        if self.is_spatial(self.stepping) {
+            return;
+        }
+        if let Some(last) = self.spatial.last_mut() {
+            std::mem::swap(last, &mut self.stepping);
+        }
+    }
+}
+
+/// `board` with each of its first 2 slots set to every one of `indices`
+fn on_board(board: &[i8], indices: Vec<i8>) -> impl Iterator<Item = Vec<i8>> + '_ {
+    let slots = board.len().min(2);
+    let count = indices.len();
+    (0..count.pow(slots as u32)).map(move |mut cell| {
+        let mut spatial = board.to_vec();
+        for index in spatial.iter_mut().take(slots) {
+            *index = indices[cell % count];
+            cell /= count;
+        }
+        spatial
+    })
+}
+
+impl FromWorld for RenderedAxes {
+    fn from_world(world: &mut World) -> Self {
+        RenderedAxes::all(**world.resource::<Dimensions>(), DEFAULT_BOARD_SIZE as i8)
+    }
+}
+
+/// V cycles the full nested view, a 3 axis slice and a 2 axis slice.
+/// `[` and `]` step the slice, C picks which hidden axis they step and X shows that axis in place of the outermost one.
+/// L cycles through the board layouts and N splits the window into 2 then 4 viewports of the stepping axis.
+pub(super) fn slice_controls(mut axes: ResMut<RenderedAxes>, input: Res<ButtonInput<KeyCode>>) {
+    if input.just_pressed(KeyCode::KeyV) {
+        let dimensions = axes.dimensions();
+        let layout = axes.layout;
+        *axes = match axes.spatial.len() {
+            3 => RenderedAxes::sliced(dimensions, 2, axes.size),
+            2 => RenderedAxes::all(dimensions, axes.size),
+            _ => RenderedAxes::sliced(dimensions, 3, axes.size),
+        };
+        axes.layout = layout;
+        info!("Rendering axes {:?}", axes.spatial);
+    }
+    if input.just_pressed(KeyCode::KeyN) {
+        let viewports = match axes.viewports() {
+            1 => 2,
+            2 => 4,
+            _ => 1,
+        };
+        axes.split(viewports);
+        info!("Split view: {:?}", axes.split);
+    }
+    if input.just_pressed(KeyCode::KeyL) {
+        axes.layout = axes.layout.next();
+        info!("Board layout: {}", axes.layout.layout().name());
+    }
+    if input.just_pressed(KeyCode::KeyC) && axes.split.is_none() {
+        axes.cycle_stepping();
+        info!("Slice controls step axis {}", axes.stepping);
+    }
+    if input.just_pressed(KeyCode::KeyX) && axes.split.is_none() {
+        axes.swap_stepping();
+        info!("Rendering axes {:?}", axes.spatial);
+    }
+    if input.just_pressed(KeyCode::BracketRight) {
+        axes.step_slice(1);
+    }
+    if input.just_pressed(KeyCode::BracketLeft) {
+        axes.step_slice(-1);
+    }
+}
+
+/// While the 4D perspective layout is shown, hold U/J, I/K and O/P to rotate it in the XW, YW and ZW planes
+pub(super) fn rotate_4d(
+    mut axes: ResMut<RenderedAxes>,
+    input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+) {
+    const SPEED: f32 = std::f32::consts::FRAC_PI_4;
+    let LayoutKind::Perspective(perspective) = axes.layout else {
+        return;
+    };
+    let spin = |positive: KeyCode, negative: KeyCode| {
+        (input.pressed(positive) as i8 - input.pressed(negative) as i8) as f32
+    };
+    let delta = Vec3::new(
+        spin(KeyCode::KeyU, KeyCode::KeyJ),
+        spin(KeyCode::KeyI, KeyCode::KeyK),
+        spin(KeyCode::KeyO, KeyCode::KeyP),
+    ) * SPEED
+        * time.delta_secs();
+    // only touch the axes when rotating, everything placed by them is moved when they change
+    if delta == Vec3::ZERO {
+        return;
+    }
+    axes.layout = LayoutKind::Perspective(Perspective4D {
+        xw: perspective.xw + delta.x,
+        yw: perspective.yw + delta.y,
+        zw: perspective.zw + delta.z,
+    });
+}
+
+/// Moves everything placed by `Position` when the rendered axes change
+pub(super) fn relayout_positions(
+    axes: Res<RenderedAxes>,
+    mut positioned: Query<(Entity, &Position, &mut Transform, Option<&mut Visibility>)>,
+    tweening: Query<(), With<MoveTween>>,
+    mut commands: Commands,
+) {
+    if !axes.is_changed() {
+        return;
+    }
+    for (entity, position, mut transform, visibility) in &mut positioned {
+        if tweening.contains(entity) {
+            commands.entity(entity).remove::<MoveTween>();
+        }
+        transform.translation = position.to_translation(&axes);
+        transform.scale = Vec3::splat(position.render_scale(&axes));
+        if let Some(mut visibility) = visibility {
+            *visibility = if position.is_visible(&axes) {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+        }
+    }
+}
+
+/// In the split view everything placed by `Position` or `OnCell` is drawn only in the viewport showing its slice,
+/// children like the selection ring go with their parent. Only what moved is looked at unless the axes changed.
+pub(super) fn assign_slice_layers(
+    axes: Res<RenderedAxes>,
+    positioned: Query<(Entity, AnyOf<(&Position, &OnCell)>, Option<&Children>)>,
+    moved: Query<
+        (Entity, AnyOf<(&Position, &OnCell)>, Option<&Children>),
+        Or<(Changed<Position>, Changed<OnCell>, Changed<Children>)>,
+    >,
+    layers: Query<&RenderLayers>,
+    mut commands: Commands,
+) {
+    if axes.split.is_none() && !axes.is_changed() {
+        return;
+    }
+    let entities: Box<dyn Iterator<Item = _>> = if axes.is_changed() {
+        Box::new(positioned.iter())
+    } else {
+        Box::new(moved.iter())
+    };
+    for (entity, (position, on_cell), children) in entities {
+        let Some(position) = position.or(on_cell.map(|on_cell| &on_cell.0)) else {
+            continue;
+        };
+        let wanted = axes
+            .split
+            .as_ref()
+            .map(|_| SliceViewport(axes.viewport(position).unwrap_or(0)).layer());
+        let children = children.into_iter().flat_map(|children| children.iter());
+        for target in std::iter::once(entity).chain(children) {
+            match (&wanted, layers.get(target).ok()) {
+                (Some(wanted), Some(current)) if wanted == current => {}
+                (Some(wanted), _) => {
+                    commands.entity(target).insert(wanted.clone());
+                }
+                (None, Some(_)) => {
+                    commands.entity(target).remove::<RenderLayers>();
+                }
+                (None, None) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+
+/// Where cells go in the world given their index on each laid out axis of a board with `size` cells along every axis.
+/// Cells are one unit across at scale 1, most layouts draw the first laid out axis as a strip of cells along -Z.
+/// A 2D board is drawn inside the box around its corner cells, so it can be skipped without looking at every cell.
+pub trait DimensionLayout: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+    /// world translation of the cell at `spatial`, indexed by laid out axis
+    fn translation(&self, spatial: &[i8], size: i8) -> Vec3;
+    /// how large the cell at `spatial` is drawn
+    fn scale(&self, _spatial: &[i8], _size: i8) -> f32 {
+        1.
+    }
+    /// The step each of the first `slots` laid out axes moves a cell by, for layouts where a cell's translation
+    /// is the sum of them and every step is along X, Y or Z. Picking uses them to find the cell under a point
+    /// rather then trying every cell.
+    fn steps(&self, _slots: usize, _size: i8) -> Option<Vec<Vec3>> {
+        None
+    }
+}
+
+/// The built in layouts, selectable at runtime through `RenderedAxes::layout`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect)]
+pub enum LayoutKind {
+    #[default]
+    NestedGrid,
+    LinearStrip,
+    Towers,
+    Tesseract,
+    Perspective(Perspective4D),
+}
+
+impl LayoutKind {
+    pub const ALL: [LayoutKind; 5] = [
+        LayoutKind::NestedGrid,
+        LayoutKind::LinearStrip,
+        LayoutKind::Towers,
+        LayoutKind::Tesseract,
+        LayoutKind::Perspective(Perspective4D::new()),
+    ];
+
+    pub fn layout(&self) -> &dyn DimensionLayout {
+        match self {
+            LayoutKind::NestedGrid => &NestedGrid,
+            LayoutKind::LinearStrip => &LinearStrip,
+            LayoutKind::Towers => &Towers,
+            LayoutKind::Tesseract => &Tesseract,
+            LayoutKind::Perspective(perspective) => perspective,
+        }
+    }
+
+    /// The same layout ignoring any state like rotation
+    pub fn same_kind(&self, other: &LayoutKind) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    pub fn next(&self) -> LayoutKind {
+        let index = Self::ALL
+            .iter()
+            .position(|l| l.same_kind(self))
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+fn sum_steps(spatial: &[i8], step: impl Fn(usize) -> Vec3) -> Vec3 {
+    spatial
+        .iter()
+        .enumerate()
+        .map(|(slot, &i)| step(slot + 1) * i as f32)
+        .sum()
+}
+
+/// Every 2D board side by side, stacked 5 apart, and each pair of dimensions past that
+/// lays out a copy of the grid below it for every cell along Z then X
+pub struct NestedGrid;
+
+impl DimensionLayout for NestedGrid {
+    fn name(&self) -> &'static str {
+        "Nested Grid"
+    }
+
+    fn translation(&self, spatial: &[i8], size: i8) -> Vec3 {
+        sum_steps(spatial, |dimension| {
+            render_dimension_step_size(dimension, size)
+        })
+    }
+
+    fn steps(&self, slots: usize, size: i8) -> Option<Vec<Vec3>> {
+        Some(
+            (1..=slots)
+                .map(|dimension| render_dimension_step_size(dimension, size))
+                .collect(),
+        )
+    }
+}
+
+/// Past the 3rd dimension each pair of dimensions lays out a copy of the grid below it for every cell
+/// along Z then X, with a gap of one cell between the copies
+pub fn render_dimension_step_size(dimension: usize, size: i8) -> Vec3 {
+    match dimension {
+        0 => Vec3::ZERO,
+        1 => Vec3::NEG_Z,
+        2 => Vec3::X,
+        3 => Vec3::Y * 5.,
+        _ => {
+            let scale = (size as f32 + 1.).powi((dimension as i32 - 2) / 2);
+            if dimension.is_multiple_of(2) {
+                Vec3::Z * scale
+            } else {
+                Vec3::X * scale
+            }
+        }
+    }
+}
+
+/// Every 2D board in one long row along X
+pub struct LinearStrip;
+
+impl DimensionLayout for LinearStrip {
+    fn name(&self) -> &'static str {
+        "Linear Strip"
+    }
+
+    fn translation(&self, spatial: &[i8], size: i8) -> Vec3 {
+        let cells = size as f32;
+        let board = spatial
+            .iter()
+            .skip(2)
+            .rev()
+            .fold(0., |board, &i| board * cells + i as f32);
+        sum_steps(&spatial[..spatial.len().min(2)], |dimension| {
+            render_dimension_step_size(dimension, size)
+        }) + Vec3::X * (cells + 1.) * board
+    }
+
+    fn steps(&self, slots: usize, size: i8) -> Option<Vec<Vec3>> {
+        let cells = size as f32;
+        Some(
+            (0..slots)
+                .map(|slot| match slot {
+                    0 | 1 => render_dimension_step_size(slot + 1, size),
+                    _ => Vec3::X * (cells + 1.) * cells.powi(slot as i32 - 2),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The first 3 axes as a tower of boards, with every tower on a flat grid
+pub struct Towers;
+
+impl DimensionLayout for Towers {
+    fn name(&self) -> &'static str {
+        "Stacked Towers"
+    }
+
+    fn translation(&self, spatial: &[i8], size: i8) -> Vec3 {
+        sum_steps(spatial, |dimension| Self::step(dimension, size))
+    }
+
+    fn steps(&self, slots: usize, size: i8) -> Option<Vec<Vec3>> {
+        Some(
+            (1..=slots)
+                .map(|dimension| Self::step(dimension, size))
+                .collect(),
+        )
+    }
+}
+
+impl Towers {
+    fn step(dimension: usize, size: i8) -> Vec3 {
+        match dimension {
+            3 => Vec3::Y * 2.,
+            // a flat grid, every pair of axes is the board size times the pair below it
+            4.. => {
+                let cells = size as f32;
+                let scale = (cells + 1.) * cells.powi((dimension as i32 - 4) / 2);
+                if dimension.is_multiple_of(2) {
+                    Vec3::Z * scale
+                } else {
+                    Vec3::X * scale
+                }
+            }
+            _ => render_dimension_step_size(dimension, size),
+        }
+    }
+}
+
+/// The first 3 axes as a cube, the 4th shrinks it into the one before like a tesseract drawn
+/// cell within cell, and any axes past that place copies of the whole tesseract side by side
+pub struct Tesseract;
+
+impl Tesseract {
+    fn center(size: i8) -> Vec3 {
+        let middle = (size - 1) as f32 * 0.5;
+        Vec3::new(middle, middle * 1.5, -middle)
+    }
+
+    fn cube_scale(w: i8) -> f32 {
+        1. / (1. + w as f32 * 0.3)
+    }
+}
+
+impl DimensionLayout for Tesseract {
+    fn name(&self) -> &'static str {
+        "Tesseract Projection"
+    }
+
+    fn translation(&self, spatial: &[i8], size: i8) -> Vec3 {
+        let cube = sum_steps(
+            &spatial[..spatial.len().min(3)],
+            |dimension| match dimension {
+                3 => Vec3::Y * 1.5,
+                _ => render_dimension_step_size(dimension, size),
+            },
+        );
+        let scale = self.scale(spatial, size);
+        let copies = spatial.iter().skip(4).enumerate().map(|(i, &index)| {
+            let step = if i % 2 == 0 { Vec3::X } else { Vec3::Z };
+            let cells = size as f32;
+            step * (cells + 4.) * cells.powi(i as i32 / 2) * index as f32
+        });
+        let center = Self::center(size);
+        center + (cube - center) * scale + copies.sum::<Vec3>()
+    }
+
+    fn scale(&self, spatial: &[i8], _size: i8) -> f32 {
+        Self::cube_scale(spatial.get(3).copied().unwrap_or(0))
+    }
+}
+
+/// The first 4 axes as a real 4D object, rotated in the planes that mix in W and then
+/// projected into 3D with perspective so cells further along W are drawn smaller and closer together.
+/// Axes past the 4th place copies side by side like `Tesseract`.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct Perspective4D {
+    pub xw: f32,
+    pub yw: f32,
+    pub zw: f32,
+}
+
+impl Default for Perspective4D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Perspective4D {
+    /// distance of the 4D eye from the center of the board along W
+    const EYE: f32 = 16.;
+    const SPACING: f32 = 1.5;
+
+    pub const fn new() -> Self {
+        Self {
+            xw: 0.,
+            yw: 0.,
+            zw: 0.,
+        }
+    }
+
+    fn middle(size: i8) -> f32 {
+        (size - 1) as f32 * 0.5
+    }
+
+    /// The cell rotated in 4D, centred on the origin
+    fn rotated(&self, spatial: &[i8], size: i8) -> Vec4 {
+        let middle = Self::middle(size);
+        let axis =
+            |slot: usize| (spatial.get(slot).copied().unwrap_or(0) as f32 - middle) * Self::SPACING;
+        let mut point = Vec4::new(axis(1), axis(2), -axis(0), axis(3));
+        let rotate = |a: f32, b: f32, angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            (a * cos - b * sin, a * sin + b * cos)
+        };
+        (point.x, point.w) = rotate(point.x, point.w, self.xw);
+        (point.y, point.w) = rotate(point.y, point.w, self.yw);
+        (point.z, point.w) = rotate(point.z, point.w, self.zw);
+        point
+    }
+
+    fn perspective(w: f32) -> f32 {
+        Self::EYE / (Self::EYE - w).max(1.)
+    }
+}
+
+impl DimensionLayout for Perspective4D {
+    fn name(&self) -> &'static str {
+        "4D Perspective"
+    }
+
+    fn translation(&self, spatial: &[i8], size: i8) -> Vec3 {
+        let point = self.rotated(spatial, size);
+        let copies = spatial.iter().skip(4).enumerate().map(|(i, &index)| {
+            let step = if i % 2 == 0 { Vec3::X } else { Vec3::Z };
+            let cells = size as f32;
+            step * cells * 4. * cells.powi(i as i32 / 2) * index as f32
+        });
+        let middle = Self::middle(size);
+        Vec3::new(middle, middle, -middle)
+            + point.truncate() * Self::perspective(point.w)
+            + copies.sum::<Vec3>()
+    }
+
+    fn scale(&self, spatial: &[i8], size: i8) -> f32 {
+        Self::perspective(self.rotated(spatial, size).w)
+    }
+}
@@ -0,0 +1,136 @@
+use bevy::{
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+use crate::board::BoardResource;
+
+/// Cells are a `StandardMaterial` with every slice past the first 2D board tinted its own colour
+pub type CellMaterial = ExtendedMaterial<StandardMaterial, SliceTint>;
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone, Default)]
+pub struct SliceTint {
+    /// 0 draws the plain theme colours, 1 blends half way to the slice colour
+    #[uniform(100)]
+    pub strength: f32,
+}
+
+impl MaterialExtension for SliceTint {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/slice_tint.wgsl".into()
+    }
+}
+
+/// The colours the board is drawn in, change it at runtime and the cells follow
+#[derive(Resource, Clone, Debug, PartialEq, Reflect)]
+pub struct BoardTheme {
+    pub name: &'static str,
+    pub light: Color,
+    pub dark: Color,
+    pub tint_slices: bool,
+}
+
+impl BoardTheme {
+    const SLICE_TINT: f32 = 0.6;
+
+    pub const CLASSIC: BoardTheme = BoardTheme {
+        name: "Classic",
+        light: Color::WHITE,
+        dark: Color::BLACK,
+        tint_slices: false,
+    };
+
+    pub const WOOD: BoardTheme = BoardTheme {
+        name: "Wood",
+        light: Color::srgb(0.94, 0.85, 0.71),
+        dark: Color::srgb(0.71, 0.53, 0.39),
+        tint_slices: false,
+    };
+
+    pub const SLATE: BoardTheme = BoardTheme {
+        name: "Slate",
+        light: Color::srgb(0.87, 0.89, 0.9),
+        dark: Color::srgb(0.34, 0.4, 0.46),
+        tint_slices: false,
+    };
+
+    pub const ALL: [BoardTheme; 3] = [Self::CLASSIC, Self::WOOD, Self::SLATE];
+
+    fn material(&self, color: Color) -> CellMaterial {
+        CellMaterial {
+            base: StandardMaterial {
+                base_color: color,
+                perceptual_roughness: 0.8,
+                ..Default::default()
+            },
+            extension: SliceTint {
+                strength: if self.tint_slices {
+                    Self::SLICE_TINT
+                } else {
+                    0.
+                },
+            },
+        }
+    }
+
+    pub(super) fn light_material(&self) -> CellMaterial {
+        self.material(self.light)
+    }
+
+    pub(super) fn dark_material(&self) -> CellMaterial {
+        self.material(self.dark)
+    }
+}
+
+impl Default for BoardTheme {
+    fn default() -> Self {
+        Self::CLASSIC
+    }
+}
+
+/// The hue the slice tint shader gives a cell on a board of `size`, every 2D board gets its own
+pub(super) fn slice_hue(position: &[i8], size: i8) -> f32 {
+    let board = position
+        .iter()
+        .skip(2)
+        .fold(0., |board, &index| board * size as f32 + index as f32 + 1.);
+    // the golden ratio spreads neighbouring boards far apart on the colour wheel
+    (board * 0.618_034f32).fract()
+}
+
+/// B cycles the board themes and G toggles tinting each slice
+pub(super) fn theme_controls(mut theme: ResMut<BoardTheme>, input: Res<ButtonInput<KeyCode>>) {
+    if input.just_pressed(KeyCode::KeyB) {
+        let index = BoardTheme::ALL
+            .iter()
+            .position(|t| t.name == theme.name)
+            .map_or(0, |i| (i + 1) % BoardTheme::ALL.len());
+        let tint_slices = theme.tint_slices;
+        *theme = BoardTheme {
+            tint_slices,
+            ..BoardTheme::ALL[index].clone()
+        };
+        info!("Board theme: {}", theme.name);
+    }
+    if input.just_pressed(KeyCode::KeyG) {
+        theme.tint_slices = !theme.tint_slices;
+    }
+}
+
+/// Recolour the cell materials in place, the board mesh doesn't need rebuilding
+pub(super) fn apply_theme(
+    theme: Res<BoardTheme>,
+    resource: Res<BoardResource>,
+    mut materials: ResMut<Assets<CellMaterial>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    if let Some(light) = materials.get_mut(&resource.light_material) {
+        *light = theme.light_material();
+    }
+    if let Some(dark) = materials.get_mut(&resource.dark_material) {
+        *dark = theme.dark_material();
+    }
+}
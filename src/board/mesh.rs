@@ -0,0 +1,100 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    platform::collections::HashMap,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+};
+
+use crate::board::{BoardResource, CellMaterial, Position, RenderedAxes, material::slice_hue};
+
+/// Vertices for every quad drawn with one material, merged into a single mesh
+#[derive(Default)]
+struct MeshBuffers {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    /// the slice hue for the tint shader
+    slice_uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuffers {
+    /// Copy `mesh` into the buffers moved by `transform`
+    fn append(&mut self, mesh: &Mesh, transform: &Transform, hue: f32) {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return;
+        };
+        let start = self.positions.len() as u32;
+        self.positions.extend(
+            positions
+                .iter()
+                .map(|p| transform.transform_point(Vec3::from_array(*p)).to_array()),
+        );
+        match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) => {
+                self.normals.extend_from_slice(normals)
+            }
+            _ => self.normals.extend(positions.iter().map(|_| [0., 1., 0.])),
+        }
+        match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => self.uvs.extend_from_slice(uvs),
+            _ => self.uvs.extend(positions.iter().map(|_| [0., 0.])),
+        }
+        self.slice_uvs.extend(positions.iter().map(|_| [hue, 0.]));
+        match mesh.indices() {
+            Some(indices) => self
+                .indices
+                .extend(indices.iter().map(|i| start + i as u32)),
+            None => self
+                .indices
+                .extend((0..positions.len() as u32).map(|i| start + i)),
+        }
+    }
+
+    fn build(self) -> Mesh {
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_1, self.slice_uvs)
+            .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
+
+/// Build one mesh per material for every shown cell, so the whole board is a handful of entities.
+/// Cells are checkered by the parity of their full position, so neighbours along any axis differ.
+pub(super) fn build_board_meshes(
+    axes: &RenderedAxes,
+    resource: &BoardResource,
+    meshes: &Assets<Mesh>,
+) -> Vec<(Mesh, Handle<CellMaterial>)> {
+    let Some(cell) = meshes.get(&resource.cell_handle) else {
+        return Vec::new();
+    };
+    let mut batches = HashMap::<Handle<CellMaterial>, MeshBuffers>::default();
+    for spatial in axes.spatial_cells(0) {
+        let position = axes.full_position(&Position(spatial.clone()));
+        let material = if position.sum().is_multiple_of(2) {
+            &resource.dark_material
+        } else {
+            &resource.light_material
+        };
+        batches.entry(material.clone()).or_default().append(
+            cell,
+            &cell_transform(axes, &spatial),
+            slice_hue(&position, axes.size),
+        );
+    }
+    batches
+        .into_iter()
+        .map(|(material, buffers)| (buffers.build(), material))
+        .collect()
+}
+
+fn cell_transform(axes: &RenderedAxes, spatial: &[i8]) -> Transform {
+    let layout = axes.layout.layout();
+    Transform::from_translation(layout.translation(spatial, axes.size))
+        .with_scale(Vec3::splat(layout.scale(spatial, axes.size)))
+}
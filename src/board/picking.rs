@@ -0,0 +1,320 @@
+use bevy::{
+    math::bounding::{Aabb3d, RayCast3d},
+    picking::{
+        PickSet,
+        backend::{
+            HitData, PointerHits,
+            ray::{RayId, RayMap},
+        },
+        pointer::PointerId,
+    },
+    platform::collections::HashMap,
+    prelude::*,
+};
+
+use crate::board::{BoardRoot, Position, RenderedAxes, SliceViewport};
+
+/// Picks cells by casting pointer rays against the layout, the board is a few merged meshes
+/// so there are no cell entities to hit. Hits are reported on the board root of the camera's viewport.
+pub struct CellPickingPlugin;
+
+impl Plugin for CellPickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HoveredCell>()
+            .add_systems(PreUpdate, cell_picking_backend.in_set(PickSet::Backend));
+    }
+}
+
+/// The cell under the mouse
+#[derive(Resource, Default)]
+pub struct HoveredCell(pub Option<Position>);
+
+/// The closest shown cell the ray passes through and how far along the ray it is,
+/// cells are flat squares one unit across at scale 1
+pub fn cell_at_ray(ray: Ray3d, axes: &RenderedAxes) -> Option<(Position, f32)> {
+    if ray.direction.y.abs() < f32::EPSILON {
+        return None;
+    }
+    let layout = axes.layout.layout();
+    let closest = match layout.steps(axes.spatial.len(), axes.size) {
+        Some(steps)
+            if steps
+                .iter()
+                .all(|step| step.cmpne(Vec3::ZERO).bitmask().count_ones() <= 1) =>
+        {
+            grid_cell_at_ray(ray, &steps, axes.size)
+        }
+        _ => cell_at_ray_by_board(ray, axes),
+    };
+    closest.map(|(distance, spatial)| (axes.full_position(&Position(spatial)), distance))
+}
+
+/// For layouts with no way back from a point to a cell, only the boards the ray passes closer then the best hit so far
+/// are tried cell by cell
+fn cell_at_ray_by_board(ray: Ray3d, axes: &RenderedAxes) -> Option<(f32, Vec<i8>)> {
+    let layout = axes.layout.layout();
+    let cast = RayCast3d::from_ray(ray, f32::MAX);
+    let mut closest: Option<(f32, Vec<i8>)> = None;
+    for board in axes.boards() {
+        let (min, max) = axes.board_corners(&board).fold(
+            (Vec3::INFINITY, Vec3::NEG_INFINITY),
+            |(min, max), corner| {
+                let center = layout.translation(&corner, axes.size);
+                let half = Vec3::new(0.5, 0., 0.5) * layout.scale(&corner, axes.size);
+                (min.min(center - half), max.max(center + half))
+            },
+        );
+        // a little room for rounding, it only decides which boards are looked at
+        let bounds = Aabb3d {
+            min: (min - 0.01).into(),
+            max: (max + 0.01).into(),
+        };
+        match cast.aabb_intersection_at(&bounds) {
+            Some(entry) if closest.as_ref().is_none_or(|(best, _)| entry < *best) => {}
+            _ => continue,
+        }
+        for spatial in axes.board_cells(&board) {
+            let center = layout.translation(&spatial, axes.size);
+            let distance = (center.y - ray.origin.y) / ray.direction.y;
+            if distance < 0. || closest.as_ref().is_some_and(|(best, _)| *best <= distance) {
+                continue;
+            }
+            let offset = ray.get_point(distance) - center;
+            let half = layout.scale(&spatial, axes.size) * 0.5;
+            if offset.x.abs() <= half && offset.z.abs() <= half {
+                closest = Some((distance, spatial));
+            }
+        }
+    }
+    closest
+}
+
+/// For layouts placed by `DimensionLayout::steps`, each level of boards is hit once
+/// and the point the ray hits it at is split back into an index on every axis along X and Z
+fn grid_cell_at_ray(ray: Ray3d, steps: &[Vec3], size: i8) -> Option<(f32, Vec<i8>)> {
+    let along = |axis: usize| {
+        let mut slots = (0..steps.len())
+            .filter(|&slot| steps[slot][axis] != 0.)
+            .collect::<Vec<_>>();
+        slots.sort_by(|&a, &b| steps[b][axis].abs().total_cmp(&steps[a][axis].abs()));
+        slots
+    };
+    let (x_slots, y_slots, z_slots) = (along(0), along(1), along(2));
+    let cells = size as usize;
+    let mut closest: Option<(f32, Vec<i8>)> = None;
+    for level in 0..cells.pow(y_slots.len() as u32) {
+        let mut spatial = vec![0; steps.len()];
+        let mut rest = level;
+        for &slot in &y_slots {
+            spatial[slot] = (rest % cells) as i8;
+            rest /= cells;
+        }
+        let y = y_slots
+            .iter()
+            .map(|&slot| steps[slot].y * spatial[slot] as f32)
+            .sum::<f32>();
+        let distance = (y - ray.origin.y) / ray.direction.y;
+        if distance < 0. || closest.as_ref().is_some_and(|(best, _)| *best <= distance) {
+            continue;
+        }
+        let point = ray.get_point(distance);
+        if split_along(point.x, 0, &x_slots, steps, size, &mut spatial)
+            && split_along(point.z, 2, &z_slots, steps, size, &mut spatial)
+        {
+            closest = Some((distance, spatial));
+        }
+    }
+    closest
+}
+
+/// Split `value` into an index on each of `slots`, which step along `axis` and are sorted biggest step first.
+/// Every step is further then the cells inside it reach, so the nearest copy is the only one it can be in.
+/// False when it is between boards or off the edge.
+fn split_along(
+    mut value: f32,
+    axis: usize,
+    slots: &[usize],
+    steps: &[Vec3],
+    size: i8,
+    spatial: &mut [i8],
+) -> bool {
+    let last = (size - 1) as f32;
+    for (i, &slot) in slots.iter().enumerate() {
+        let (low, high) = slots[i + 1..].iter().fold((0., 0.), |(low, high), &inner| {
+            let reach = steps[inner][axis] * last;
+            (low + reach.min(0.), high + reach.max(0.))
+        });
+        let step = steps[slot][axis];
+        // past the last copy it can still be on its edge
+        let index = ((value - (low + high) * 0.5) / step)
+            .round()
+            .clamp(0., last);
+        spatial[slot] = index as i8;
+        value -= step * index;
+    }
+    value.abs() <= 0.5
+}
+
+fn cell_picking_backend(
+    rays: Res<RayMap>,
+    cameras: Query<(&Camera, Option<&SliceViewport>)>,
+    roots: Query<(Entity, &SliceViewport), With<BoardRoot>>,
+    axes: Res<RenderedAxes>,
+    mut hovered: ResMut<HoveredCell>,
+    mut hits: EventWriter<PointerHits>,
+    mut last: Local<HashMap<RayId, (Ray3d, Option<(Position, f32)>)>>,
+) {
+    // rays mostly stay still between frames, the cell is only found again once the ray or the axes move
+    if axes.is_changed() {
+        last.clear();
+    }
+    last.retain(|ray_id, _| rays.map.contains_key(ray_id));
+    let mut mouse = None;
+    for (ray_id, ray) in rays.iter() {
+        let Ok((camera, viewport)) = cameras.get(ray_id.camera) else {
+            continue;
+        };
+        let viewport = viewport.copied().unwrap_or(SliceViewport(0));
+        let Some((root, _)) = roots.iter().find(|(_, root)| **root == viewport) else {
+            continue;
+        };
+        let found = match last.get(ray_id) {
+            Some((last_ray, found)) if last_ray == ray => found.clone(),
+            _ => {
+                let found = cell_at_ray(*ray, &axes.for_viewport(viewport.0));
+                last.insert(*ray_id, (*ray, found.clone()));
+                found
+            }
+        };
+        let Some((cell, distance)) = found else {
+            continue;
+        };
+        let hit = HitData::new(
+            ray_id.camera,
+            distance,
+            Some(ray.get_point(distance)),
+            Some(Vec3::Y),
+        );
+        hits.write(PointerHits::new(
+            ray_id.pointer,
+            vec![(root, hit)],
+            camera.order as f32,
+        ));
+        if ray_id.pointer == PointerId::Mouse {
+            mouse = Some(cell);
+        }
+    }
+    if hovered.0 != mouse {
+        hovered.0 = mouse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{LayoutKind, Perspective4D};
+
+    fn every_cell_at_ray(ray: Ray3d, axes: &RenderedAxes) -> Option<(f32, Vec<i8>)> {
+        let layout = axes.layout.layout();
+        let mut closest: Option<(f32, Vec<i8>)> = None;
+        for spatial in axes.spatial_cells(0) {
+            let center = layout.translation(&spatial, axes.size);
+            let distance = (center.y - ray.origin.y) / ray.direction.y;
+            if distance < 0. || closest.as_ref().is_some_and(|(best, _)| *best <= distance) {
+                continue;
+            }
+            let offset = ray.get_point(distance) - center;
+            let half = layout.scale(&spatial, axes.size) * 0.5;
+            if offset.x.abs() <= half && offset.z.abs() <= half {
+                closest = Some((distance, spatial));
+            }
+        }
+        closest
+    }
+
+    #[test]
+    fn grid_layouts_pick_the_same_cell_as_trying_every_cell() {
+        for layout in [
+            LayoutKind::NestedGrid,
+            LayoutKind::LinearStrip,
+            LayoutKind::Towers,
+        ] {
+            for (dimensions, size) in (2..=5).zip([8, 5, 10, 6]) {
+                let mut axes = RenderedAxes::all(dimensions, size);
+                axes.layout = layout;
+                let steps = layout.layout().steps(axes.spatial.len(), size).unwrap();
+                // a couple of hundred cells spread over the board
+                let stride = (size as usize).pow(axes.spatial.len() as u32) / 200 + 7;
+                for (n, spatial) in axes.spatial_cells(0).enumerate().step_by(stride) {
+                    // aim near the cell, sometimes just past its edge but never on it, from above at an angle
+                    let wobble = |k: usize| ((n * k) % 13) as f32 / 10. - 0.65;
+                    let target = layout.layout().translation(&spatial, size)
+                        + Vec3::new(wobble(3), 0., wobble(5));
+                    let origin = target + Vec3::new(wobble(7) * 4.3, 41.7, wobble(11) * 3.1);
+                    let ray = Ray3d::new(origin, Dir3::new(target - origin).unwrap());
+                    let grid = grid_cell_at_ray(ray, &steps, size).map(|(_, cell)| cell);
+                    let every = every_cell_at_ray(ray, &axes).map(|(_, cell)| cell);
+                    assert_eq!(
+                        grid, every,
+                        "{layout:?} in {dimensions}D aiming at {spatial:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn skipping_boards_picks_the_same_cell_as_trying_every_cell() {
+        let rotated = Perspective4D {
+            xw: 0.4,
+            yw: -0.7,
+            zw: 1.1,
+        };
+        for layout in [
+            LayoutKind::Tesseract,
+            LayoutKind::Perspective(Perspective4D::new()),
+            LayoutKind::Perspective(rotated),
+        ] {
+            for (dimensions, size) in (2..=5).zip([8, 5, 10, 6]) {
+                let mut axes = RenderedAxes::all(dimensions, size);
+                axes.layout = layout;
+                let stride = (size as usize).pow(axes.spatial.len() as u32) / 100 + 3;
+                for (n, spatial) in axes.spatial_cells(0).enumerate().step_by(stride) {
+                    let wobble = |k: usize| ((n * k) % 13) as f32 / 10. - 0.65;
+                    let target = layout.layout().translation(&spatial, size)
+                        + Vec3::new(wobble(3), 0., wobble(5));
+                    let origin = target + Vec3::new(wobble(7) * 4.3, 41.7, wobble(11) * 3.1);
+                    let ray = Ray3d::new(origin, Dir3::new(target - origin).unwrap());
+                    let by_board = cell_at_ray_by_board(ray, &axes).map(|(_, cell)| cell);
+                    let every = every_cell_at_ray(ray, &axes).map(|(_, cell)| cell);
+                    assert_eq!(
+                        by_board, every,
+                        "{layout:?} in {dimensions}D aiming at {spatial:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_from_board_corners_hold_every_cell() {
+        for layout in LayoutKind::ALL {
+            for (dimensions, size) in (2..=4).zip([8, 5, 10]) {
+                let mut axes = RenderedAxes::all(dimensions, size);
+                axes.layout = layout;
+                let (min, max) = axes.bounds(None);
+                let layout = layout.layout();
+                for spatial in axes.spatial_cells(0) {
+                    let center = layout.translation(&spatial, size);
+                    let half = Vec3::new(0.5, 0., 0.5) * layout.scale(&spatial, size);
+                    assert!(
+                        (center - half).cmpge(min - 0.001).all()
+                            && (center + half).cmple(max + 0.001).all(),
+                        "{layout:?} in {dimensions}D, {spatial:?} is outside {min} to {max}",
+                        layout = layout.name()
+                    );
+                }
+            }
+        }
+    }
+}
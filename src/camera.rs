@@ -1,12 +1,53 @@
-use bevy::prelude::*;
+use bevy::{
+    input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit},
+    platform::collections::HashMap,
+    prelude::*,
+    render::{
+        camera::{ScalingMode, Viewport},
+        view::RenderLayers,
+    },
+    window::{PrimaryWindow, WindowResized},
+};
+
+use crate::board::{HoveredCell, LayoutKind, Position, RenderedAxes, SliceViewport};
+use crate::pieces::{CellCursor, CursorBindings, MoveAnimation, MoveMade, MoveTween, Selected};
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(bevy_flycam::NoCameraPlayerPlugin);
-        app.add_systems(Startup, spawn_camera)
-            .add_systems(Update, (update_camera_view, move_camera));
+        app.add_plugins(bevy_flycam::NoCameraPlayerPlugin)
+            // space confirms moves with the cell cursor
+            .insert_resource(bevy_flycam::KeyBindings {
+                move_ascend: KeyCode::KeyE,
+                move_descend: KeyCode::KeyQ,
+                ..Default::default()
+            });
+        app.init_resource::<CameraPreset>()
+            .init_resource::<CameraMode>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(
+                Update,
+                (
+                    (
+                        switch_mode,
+                        choose_preset,
+                        frame_preset,
+                        move_camera,
+                        orbit_with_mouse,
+                        update_camera_view,
+                        tween_camera,
+                        follow_moving_piece,
+                    )
+                        .chain(),
+                    follow_piece,
+                    split_viewports,
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                sync_slice_cameras.before(TransformSystem::TransformPropagate),
+            );
     }
 }
 
@@ -14,245 +55,483 @@ impl Plugin for CameraPlugin {
 struct BoardCamera;
 
 fn spawn_camera(mut commands: Commands) {
-    commands.spawn((
-        BoardCamera,
-        Camera3d::default(),
-        BoardCameraView::new(Value::Zero, Value::Zero, Value::Neg),
-        bevy_flycam::FlyCam,
-    ));
+    commands.spawn((BoardCamera, Camera3d::default(), BoardCameraView::default()));
 }
 
-#[derive(Component, Debug)]
-struct BoardCameraView {
-    x: Value,
-    z: Value,
-    y: Value,
+/// How the camera is driven, Tab cycles through them.
+/// Only the current mode reads camera input, so they never fight over the transform.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    /// circle the framed preset, arrows or right drag to turn and scroll to zoom
+    #[default]
+    Orbit,
+    /// WASD, E and Q to fly, the mouse looks around once the cursor is grabbed
+    FreeFly,
+    /// straight down with an orthographic projection, scroll to zoom
+    TopDown,
+    /// stays behind the selected piece, or the last piece that moved
+    FollowPiece,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Value {
-    Pos,
-    Zero,
-    Neg,
+impl CameraMode {
+    const ALL: [CameraMode; 4] = [
+        CameraMode::Orbit,
+        CameraMode::FreeFly,
+        CameraMode::TopDown,
+        CameraMode::FollowPiece,
+    ];
+
+    fn next(&self) -> CameraMode {
+        let index = Self::ALL.iter().position(|m| m == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
 }
 
-impl Value {
-    fn offset(&self) -> f32 {
-        match self {
-            Value::Pos => 16.0,
-            Value::Zero => 0.0,
-            Value::Neg => -16.0,
+/// What the camera looks at and which way it looks from
+#[derive(Component, Debug)]
+struct BoardCameraView {
+    center: Vec3,
+    distance: f32,
+    /// around Y, 0 looks along +Z
+    yaw: f32,
+    /// above the board, 0 is level with it
+    pitch: f32,
+}
+
+impl Default for BoardCameraView {
+    fn default() -> Self {
+        Self {
+            center: Vec3::splat(4.),
+            distance: 16.,
+            yaw: 0.,
+            pitch: std::f32::consts::FRAC_PI_6,
         }
     }
 }
 
 impl BoardCameraView {
-    fn new(x: Value, y: Value, z: Value) -> Self {
-        Self { x, y, z }
-    }
+    /// arrow keys turn the camera a 8th of a circle
+    const STEP: f32 = std::f32::consts::FRAC_PI_4;
+    const MAX_PITCH: f32 = 1.5;
 
-    fn transform(&self) -> Transform {
-        Transform::from_translation(
-            Vec3::new(self.x.offset(), self.y.offset(), self.z.offset()) + Vec3::splat(4.),
-        )
-        .looking_at(Vec3::splat(4.), Vec3::Y)
+    /// The direction from the center to the camera, `distance` long
+    fn offset(&self) -> Vec3 {
+        Vec3::new(
+            self.pitch.cos() * -self.yaw.sin(),
+            self.pitch.sin(),
+            -self.pitch.cos() * self.yaw.cos(),
+        ) * self.distance
     }
 
-    fn left(&mut self) {
-        match self {
-            BoardCameraView {
-                x: Value::Pos,
-                z: Value::Zero,
-                y: _,
-            } => {
-                self.z = Value::Neg;
-            }
-            BoardCameraView {
-                x: Value::Pos,
-                z: Value::Neg,
-                y: _,
-            } => {
-                self.x = Value::Zero;
-            }
-            BoardCameraView {
-                x: Value::Zero,
-                z: Value::Neg,
-                y: _,
-            } => {
-                self.x = Value::Neg;
-            }
-            BoardCameraView {
-                x: Value::Neg,
-                z: Value::Neg,
-                y: _,
-            } => {
-                self.z = Value::Zero;
+    fn transform(&self, mode: CameraMode) -> Transform {
+        match mode {
+            CameraMode::TopDown => {
+                Transform::from_translation(self.center + Vec3::Y * (self.distance + 10.))
+                    .looking_at(self.center, Vec3::NEG_Z)
             }
-            BoardCameraView {
-                x: Value::Neg,
-                z: Value::Zero,
-                y: _,
-            } => {
-                self.z = Value::Pos;
-            }
-            BoardCameraView {
-                x: Value::Neg,
-                z: Value::Pos,
-                y: _,
-            } => {
-                self.x = Value::Zero;
-            }
-            BoardCameraView {
-                x: Value::Zero,
-                z: Value::Pos,
-                y: _,
-            } => {
-                self.x = Value::Pos;
-            }
-            BoardCameraView {
-                x: Value::Pos,
-                z: Value::Pos,
-                y: _,
-            } => {
-                self.z = Value::Zero;
-            }
-            BoardCameraView {
-                x: Value::Zero,
-                z: Value::Zero,
-                y: _,
-            } => {}
+            _ => Transform::from_translation(self.center + self.offset())
+                .looking_at(self.center, Vec3::Y),
         }
     }
 
-    fn right(&mut self) {
-        match self {
-            BoardCameraView {
-                x: Value::Pos,
-                z: Value::Zero,
-                y: _,
-            } => {
-                self.z = Value::Pos;
-            }
-            BoardCameraView {
-                x: Value::Pos,
-                z: Value::Pos,
-                y: _,
-            } => {
-                self.x = Value::Zero;
-            }
-            BoardCameraView {
-                x: Value::Zero,
-                z: Value::Pos,
-                y: _,
-            } => {
-                self.x = Value::Neg;
-            }
-            BoardCameraView {
-                x: Value::Neg,
-                z: Value::Pos,
-                y: _,
-            } => {
-                self.z = Value::Zero;
-            }
-            BoardCameraView {
-                x: Value::Neg,
-                z: Value::Zero,
-                y: _,
-            } => {
-                self.z = Value::Neg;
-            }
-            BoardCameraView {
-                x: Value::Neg,
-                z: Value::Neg,
-                y: _,
-            } => {
-                self.x = Value::Zero;
-            }
-            BoardCameraView {
-                x: Value::Zero,
-                z: Value::Neg,
-                y: _,
-            } => {
-                self.x = Value::Pos;
-            }
-            BoardCameraView {
-                x: Value::Pos,
-                z: Value::Neg,
-                y: _,
-            } => {
-                self.z = Value::Zero;
-            }
-            BoardCameraView {
-                x: Value::Zero,
-                z: Value::Zero,
-                y: _,
-            } => {}
-        }
+    /// Back off far enough to fit the box from `min` to `max` on screen
+    fn frame(&mut self, min: Vec3, max: Vec3) {
+        let radius = ((max - min).length() * 0.5).max(1.);
+        self.center = (min + max) * 0.5;
+        // the default perspective has a 45 degree field of view
+        self.distance = radius / (std::f32::consts::FRAC_PI_8).sin();
     }
 
-    fn up(&mut self) {
-        match self {
-            BoardCameraView {
-                y: Value::Zero,
-                x: _,
-                z: _,
-            } => {
-                self.y = Value::Pos;
-            }
-            BoardCameraView {
-                y: Value::Neg,
-                x: _,
-                z: _,
-            } => {
-                self.y = Value::Zero;
-            }
-            _ => {}
+    fn turn(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    /// The projection for `mode`, top down shows the same area as `distance` does in perspective
+    fn projection(&self, mode: CameraMode) -> Projection {
+        match mode {
+            CameraMode::TopDown => Projection::Orthographic(OrthographicProjection {
+                scaling_mode: ScalingMode::FixedVertical {
+                    viewport_height: self.distance * (std::f32::consts::FRAC_PI_8).tan() * 2.,
+                },
+                ..OrthographicProjection::default_3d()
+            }),
+            _ => Projection::Perspective(PerspectiveProjection::default()),
         }
     }
+}
 
-    fn down(&mut self) {
-        match self {
-            BoardCameraView {
-                y: Value::Zero,
-                x: _,
-                z: _,
-            } => {
-                self.y = Value::Neg;
-            }
-            BoardCameraView {
-                y: Value::Pos,
-                x: _,
-                z: _,
-            } => {
-                self.y = Value::Zero;
-            }
-            _ => {}
+/// Tab changes mode, the flycam only moves the camera while it has a `FlyCam`
+fn switch_mode(
+    input: Res<ButtonInput<KeyCode>>,
+    mut mode: ResMut<CameraMode>,
+    camera: Single<(Entity, &mut BoardCameraView, &mut Projection), With<BoardCamera>>,
+    mut commands: Commands,
+) {
+    if !input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    *mode = mode.next();
+    info!("Camera mode: {:?}", *mode);
+    let (entity, mut view, mut projection) = camera.into_inner();
+    *projection = view.projection(*mode);
+    // a glide from the last mode would fight the new one over the transform
+    commands.entity(entity).remove::<CameraTween>();
+    if *mode == CameraMode::FreeFly {
+        commands.entity(entity).insert(bevy_flycam::FlyCam);
+    } else {
+        commands.entity(entity).remove::<bevy_flycam::FlyCam>();
+    }
+    // glide back from wherever the last mode left the camera
+    view.set_changed();
+}
+
+/// What the camera frames, F1 the whole board, F2 the 2D board being played on and F3 the selected piece
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraPreset {
+    #[default]
+    Board,
+    Slice,
+    Piece,
+}
+
+fn choose_preset(input: Res<ButtonInput<KeyCode>>, mut preset: ResMut<CameraPreset>) {
+    let chosen = if input.just_pressed(KeyCode::F1) {
+        CameraPreset::Board
+    } else if input.just_pressed(KeyCode::F2) {
+        CameraPreset::Slice
+    } else if input.just_pressed(KeyCode::F3) {
+        CameraPreset::Piece
+    } else {
+        return;
+    };
+    // always marks it changed, so pressing the same preset again reframes it
+    *preset = chosen;
+}
+
+/// The bounds of the whole board and of each 2D board framed so far, kept until the axes change
+#[derive(Default)]
+struct FramedBounds(HashMap<Option<Vec<i8>>, (Vec3, Vec3)>);
+
+/// Frame the preset from the bounds of the active layout, again whenever the layout changes
+fn frame_preset(
+    preset: Res<CameraPreset>,
+    axes: Res<RenderedAxes>,
+    selected: Query<&Position, With<Selected>>,
+    cursor: Res<CellCursor>,
+    hovered: Res<HoveredCell>,
+    mode: Res<CameraMode>,
+    camera: Single<(&mut BoardCameraView, &mut Projection), With<BoardCamera>>,
+    mut shown: Local<Option<(Vec<usize>, Vec<i8>, LayoutKind)>>,
+    mut bounds: Local<FramedBounds>,
+) {
+    if axes.is_changed() {
+        bounds.0.clear();
+    }
+    // rotating a layout changes the axes every frame, only reframe for a different view
+    let same_view = shown.as_ref().is_some_and(|(spatial, slice, layout)| {
+        *spatial == axes.spatial && *slice == axes.slice && layout.same_kind(&axes.layout)
+    });
+    if !preset.is_changed() && same_view {
+        return;
+    }
+    *shown = Some((axes.spatial.clone(), axes.slice.clone(), axes.layout));
+    let (mut camera, mut projection) = camera.into_inner();
+    // the cell the player is working with, for framing around it
+    let focus = selected
+        .iter()
+        .next()
+        .or(cursor.position.as_ref())
+        .or(hovered.0.as_ref());
+    let mut bounds = |board: Option<&Position>| {
+        let key = board.map(|board| {
+            axes.spatial_position(board)
+                .split_off(2.min(axes.spatial.len()))
+        });
+        *bounds.0.entry(key).or_insert_with(|| axes.bounds(board))
+    };
+    match (*preset, focus) {
+        (CameraPreset::Board, _) | (_, None) => {
+            let (min, max) = bounds(None);
+            camera.frame(min, max);
+        }
+        (CameraPreset::Slice, Some(focus)) => {
+            let (min, max) = bounds(Some(focus));
+            camera.frame(min, max);
+        }
+        (CameraPreset::Piece, Some(focus)) => {
+            let center = focus.to_translation(&axes);
+            camera.frame(center - Vec3::splat(2.), center + Vec3::splat(2.));
         }
     }
+    if *mode == CameraMode::TopDown {
+        *projection = camera.projection(*mode);
+    }
+}
+
+/// Glides the camera to a new view instead of jumping
+#[derive(Component)]
+struct CameraTween {
+    from: Transform,
+    to: Transform,
+    elapsed: f32,
 }
 
+/// Orbit and top down place the camera from the view, the other modes move it themselves
 fn update_camera_view(
-    mut camera: Populated<(&mut Transform, &BoardCameraView), Changed<BoardCameraView>>,
+    camera: Populated<(Entity, &Transform, &BoardCameraView), Changed<BoardCameraView>>,
+    mode: Res<CameraMode>,
+    mut commands: Commands,
+) {
+    if !matches!(*mode, CameraMode::Orbit | CameraMode::TopDown) {
+        return;
+    }
+    for (entity, transform, view) in camera.iter() {
+        commands.entity(entity).insert(CameraTween {
+            from: *transform,
+            to: view.transform(*mode),
+            elapsed: 0.,
+        });
+    }
+}
+
+fn tween_camera(
+    mut camera: Query<(Entity, &mut Transform, &mut CameraTween)>,
+    time: Res<Time>,
+    mut commands: Commands,
 ) {
-    for (mut transform, view) in camera.iter_mut() {
-        *transform = view.transform();
+    const DURATION: f32 = 0.6;
+    for (entity, mut transform, mut tween) in &mut camera {
+        tween.elapsed += time.delta_secs();
+        let t = (tween.elapsed / DURATION).clamp(0., 1.);
+        let eased = t * t * (3. - 2. * t);
+        transform.translation = tween.from.translation.lerp(tween.to.translation, eased);
+        transform.rotation = tween.from.rotation.slerp(tween.to.rotation, eased);
+        if t >= 1. {
+            commands.entity(entity).remove::<CameraTween>();
+        }
     }
 }
 
 fn move_camera(
     mut camera: Single<&mut BoardCameraView, With<BoardCamera>>,
     input: Res<ButtonInput<KeyCode>>,
+    cursor: Res<CursorBindings>,
+    mode: Res<CameraMode>,
+) {
+    if *mode != CameraMode::Orbit || cursor.modifier_held(&input) {
+        return;
+    }
+    if input.just_pressed(KeyCode::ArrowLeft) {
+        camera.turn(-BoardCameraView::STEP, 0.);
+    }
+    if input.just_pressed(KeyCode::ArrowRight) {
+        camera.turn(BoardCameraView::STEP, 0.);
+    }
+    if input.just_pressed(KeyCode::ArrowUp) {
+        camera.turn(0., BoardCameraView::STEP);
+    }
+    if input.just_pressed(KeyCode::ArrowDown) {
+        camera.turn(0., -BoardCameraView::STEP);
+    }
+}
+
+/// Right drag turns the orbit and scrolling zooms, both follow the mouse straight away rather then gliding
+fn orbit_with_mouse(
+    mouse: Res<ButtonInput<MouseButton>>,
+    motion: Res<AccumulatedMouseMotion>,
+    scroll: Res<AccumulatedMouseScroll>,
+    mode: Res<CameraMode>,
+    camera: Single<
+        (
+            Entity,
+            &mut Transform,
+            &mut BoardCameraView,
+            &mut Projection,
+        ),
+        With<BoardCamera>,
+    >,
+    mut commands: Commands,
+) {
+    const SENSITIVITY: f32 = 0.005;
+    if !matches!(*mode, CameraMode::Orbit | CameraMode::TopDown) {
+        return;
+    }
+    let (entity, mut transform, mut view, mut projection) = camera.into_inner();
+    let view = view.bypass_change_detection();
+    let mut moved = false;
+    if *mode == CameraMode::Orbit && mouse.pressed(MouseButton::Right) && motion.delta != Vec2::ZERO
+    {
+        view.turn(motion.delta.x * SENSITIVITY, motion.delta.y * SENSITIVITY);
+        moved = true;
+    }
+    if scroll.delta.y != 0. {
+        let lines = match scroll.unit {
+            MouseScrollUnit::Line => scroll.delta.y,
+            MouseScrollUnit::Pixel => scroll.delta.y / 20.,
+        };
+        view.distance = (view.distance * (1. - lines * 0.1)).max(1.);
+        if *mode == CameraMode::TopDown {
+            *projection = view.projection(*mode);
+        }
+        moved = true;
+    }
+    if moved {
+        *transform = view.transform(*mode);
+        commands.entity(entity).remove::<CameraTween>();
+    }
+}
+
+/// Keeps the camera on pieces that jump between boards, these can move further then the camera can see.
+/// Runs after the orbit's glide so it has the last word while the piece moves, then glides back to the orbit.
+fn follow_moving_piece(
+    moving: Query<(&Transform, &MoveTween), Without<BoardCamera>>,
+    settings: Res<MoveAnimation>,
+    mode: Res<CameraMode>,
+    camera: Single<(Entity, &mut Transform, &BoardCameraView), With<BoardCamera>>,
+    mut following: Local<bool>,
+    mut commands: Commands,
+) {
+    if !settings.follow_camera || *mode != CameraMode::Orbit {
+        *following = false;
+        return;
+    }
+    let (entity, mut transform, view) = camera.into_inner();
+    match moving.iter().find(|(_, tween)| tween.crosses_dimensions) {
+        Some((piece, _)) => {
+            let target = piece.translation;
+            let offset = view.offset().normalize_or_zero() * 16.;
+            *transform = Transform::from_translation(target + offset).looking_at(target, Vec3::Y);
+            commands.entity(entity).remove::<CameraTween>();
+            *following = true;
+        }
+        None if *following => {
+            commands.entity(entity).insert(CameraTween {
+                from: *transform,
+                to: view.transform(*mode),
+                elapsed: 0.,
+            });
+            *following = false;
+        }
+        None => {}
+    }
+}
+
+/// In follow mode the camera sits behind the selected piece, or the last one to move
+fn follow_piece(
+    mode: Res<CameraMode>,
+    mut moves: EventReader<MoveMade>,
+    mut last_moved: Local<Option<Entity>>,
+    selected: Query<Entity, With<Selected>>,
+    pieces: Query<&Transform, Without<BoardCamera>>,
+    time: Res<Time>,
+    mut camera: Single<(&mut Transform, &BoardCameraView), With<BoardCamera>>,
+) {
+    if let Some(made) = moves.read().last() {
+        *last_moved = Some(made.entity);
+    }
+    if *mode != CameraMode::FollowPiece {
+        return;
+    }
+    let Some(target) = selected
+        .iter()
+        .next()
+        .or(*last_moved)
+        .and_then(|piece| pieces.get(piece).ok())
+    else {
+        return;
+    };
+    let offset = camera.1.offset().normalize_or_zero() * 8.;
+    let wanted = Transform::from_translation(target.translation + offset)
+        .looking_at(target.translation, Vec3::Y);
+    // ease towards the piece so switching pieces doesn't snap
+    let blend = 1. - (-time.delta_secs() * 6.).exp();
+    camera.0.translation = camera.0.translation.lerp(wanted.translation, blend);
+    camera.0.rotation = camera.0.rotation.slerp(wanted.rotation, blend);
+}
+
+/// Draws one of the extra viewports of the split view
+#[derive(Component)]
+struct SliceCamera;
+
+/// Tile the window with a viewport per slice of the split view, the board camera takes the first
+fn split_viewports(
+    axes: Res<RenderedAxes>,
+    mut resized: EventReader<WindowResized>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    board_camera: Single<(Entity, &mut Camera), With<BoardCamera>>,
+    mut slice_cameras: Query<(Entity, &SliceViewport, &mut Camera), Without<BoardCamera>>,
+    mut commands: Commands,
 ) {
-    if input.any_just_pressed([KeyCode::ArrowLeft]) {
-        camera.left();
+    if resized.read().count() == 0 && !axes.is_changed() {
+        return;
+    }
+    let (entity, mut camera) = board_camera.into_inner();
+    if axes.split.is_none() {
+        if camera.viewport.is_some() {
+            camera.viewport = None;
+            commands
+                .entity(entity)
+                .remove::<(SliceViewport, RenderLayers)>();
+        }
+        for (slice_camera, ..) in &slice_cameras {
+            commands.entity(slice_camera).despawn();
+        }
+        return;
     }
-    if input.any_just_pressed([KeyCode::ArrowRight]) {
-        camera.right();
+    // 2 side by side, 4 in a square
+    let count = axes.viewports() as u32;
+    let columns = (count as f32).sqrt().ceil() as u32;
+    let rows = count.div_ceil(columns);
+    let size = (window.physical_size() / UVec2::new(columns, rows)).max(UVec2::ONE);
+    let viewport = |index: u32| Viewport {
+        physical_position: UVec2::new(index % columns, index / columns) * size,
+        physical_size: size,
+        ..Default::default()
+    };
+    camera.viewport = Some(viewport(0));
+    commands
+        .entity(entity)
+        .insert((SliceViewport(0), SliceViewport(0).layer().with(0)));
+    let mut spawned = vec![false; count as usize];
+    for (slice_camera, index, mut camera) in &mut slice_cameras {
+        match spawned.get_mut(index.0) {
+            Some(spawned) => {
+                camera.viewport = Some(viewport(index.0 as u32));
+                *spawned = true;
+            }
+            None => commands.entity(slice_camera).despawn(),
+        }
     }
-    if input.any_just_pressed([KeyCode::ArrowUp]) {
-        camera.up();
+    for index in (1..count).filter(|&index| !spawned[index as usize]) {
+        let slice = SliceViewport(index as usize);
+        commands.spawn((
+            Name::new("Slice Camera"),
+            SliceCamera,
+            Camera3d::default(),
+            Camera {
+                order: index as isize,
+                viewport: Some(viewport(index)),
+                ..Default::default()
+            },
+            slice,
+            // layer 0 has everything not tied to a slice, like move highlights and the cursor
+            slice.layer().with(0),
+        ));
     }
-    if input.any_just_pressed([KeyCode::ArrowDown]) {
-        camera.down();
+}
+
+/// The slice cameras look from wherever the board camera is, so the viewports line up
+fn sync_slice_cameras(
+    board_camera: Single<(&Transform, &Projection), With<BoardCamera>>,
+    mut slice_cameras: Query<
+        (&mut Transform, &mut Projection),
+        (With<SliceCamera>, Without<BoardCamera>),
+    >,
+) {
+    let (transform, projection) = *board_camera;
+    for (mut slice_transform, mut slice_projection) in &mut slice_cameras {
+        *slice_transform = *transform;
+        *slice_projection = projection.clone();
     }
 }
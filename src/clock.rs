@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+
+use crate::game::{AppState, GameConfig, InGame, NewGameSet};
+use crate::pieces::{MoveMade, Team};
+use crate::rules::GameOutcome;
+
+/// Runs the clock of the side to move, a side that runs out of time loses
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChessClock>()
+            .add_systems(OnEnter(InGame), reset_clock.in_set(NewGameSet::Configure))
+            .add_systems(
+                Update,
+                (finish_move, run_clock)
+                    .chain()
+                    .run_if(in_state(AppState::Playing)),
+            );
+    }
+}
+
+/// How much time each side gets, all times are in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub enum TimeControl {
+    #[default]
+    Unlimited,
+    /// the whole game has to be played in `base`
+    SuddenDeath { base: f32 },
+    /// `increment` is added after every move
+    Fischer { base: f32, increment: f32 },
+    /// up to `delay` of the time spent on a move is given back after it
+    Bronstein { base: f32, delay: f32 },
+    /// every move has to be made within `limit`, unused time doesn't carry over
+    PerMove { limit: f32 },
+}
+
+impl TimeControl {
+    /// The time controls offered in the menu
+    pub const PRESETS: [TimeControl; 5] = [
+        TimeControl::Unlimited,
+        TimeControl::SuddenDeath { base: 600. },
+        TimeControl::Fischer {
+            base: 300.,
+            increment: 3.,
+        },
+        TimeControl::Bronstein {
+            base: 300.,
+            delay: 5.,
+        },
+        TimeControl::PerMove { limit: 30. },
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            TimeControl::Unlimited => String::from("Unlimited"),
+            TimeControl::SuddenDeath { base } => format!("{} sudden death", format_time(*base)),
+            TimeControl::Fischer { base, increment } => {
+                format!("{} + {increment}s Fischer", format_time(*base))
+            }
+            TimeControl::Bronstein { base, delay } => {
+                format!("{} with {delay}s Bronstein delay", format_time(*base))
+            }
+            TimeControl::PerMove { limit } => format!("{limit}s per move"),
+        }
+    }
+
+    /// What each side starts the game with, `None` when there is no clock
+    fn starting_time(&self) -> Option<f32> {
+        match *self {
+            TimeControl::Unlimited => None,
+            TimeControl::SuddenDeath { base }
+            | TimeControl::Fischer { base, .. }
+            | TimeControl::Bronstein { base, .. } => Some(base),
+            TimeControl::PerMove { limit } => Some(limit),
+        }
+    }
+}
+
+/// Minutes and seconds, with tenths once there are under 10 seconds left
+pub fn format_time(seconds: f32) -> String {
+    let seconds = seconds.max(0.);
+    if seconds < 10. {
+        format!("0:0{seconds:.1}")
+    } else {
+        let whole = seconds as u32;
+        format!("{}:{:02}", whole / 60, whole % 60)
+    }
+}
+
+/// The time each side has left
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ChessClock {
+    pub control: TimeControl,
+    pub white: f32,
+    pub black: f32,
+    /// how long the side to move has spent on this move
+    pub spent: f32,
+    /// half moves played, clocks sent over the network are matched up by this
+    pub moves: usize,
+}
+
+impl ChessClock {
+    pub fn new(control: TimeControl) -> Self {
+        let start = control.starting_time().unwrap_or_default();
+        Self {
+            control,
+            white: start,
+            black: start,
+            spent: 0.,
+            moves: 0,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.control.starting_time().is_some()
+    }
+
+    pub fn remaining(&self, team: Team) -> f32 {
+        match team {
+            Team::White => self.white,
+            Team::Black => self.black,
+        }
+    }
+
+    fn remaining_mut(&mut self, team: Team) -> &mut f32 {
+        match team {
+            Team::White => &mut self.white,
+            Team::Black => &mut self.black,
+        }
+    }
+
+    /// `team` just moved, apply the time control and start the other side's move
+    fn moved(&mut self, team: Team) {
+        let spent = std::mem::take(&mut self.spent);
+        self.moves += 1;
+        match self.control {
+            TimeControl::Unlimited | TimeControl::SuddenDeath { .. } => {}
+            TimeControl::Fischer { increment, .. } => *self.remaining_mut(team) += increment,
+            TimeControl::Bronstein { delay, .. } => *self.remaining_mut(team) += spent.min(delay),
+            TimeControl::PerMove { limit } => *self.remaining_mut(team) = limit,
+        }
+    }
+}
+
+fn reset_clock(config: Res<GameConfig>, mut clock: ResMut<ChessClock>) {
+    *clock = ChessClock::new(config.time_control);
+}
+
+/// The turn has already passed to the other side when `MoveMade` is sent
+fn finish_move(mut moves: EventReader<MoveMade>, mut clock: ResMut<ChessClock>) {
+    for made in moves.read() {
+        clock.moved(made.team);
+    }
+}
+
+fn run_clock(
+    time: Res<Time>,
+    turn: Res<Team>,
+    mut clock: ResMut<ChessClock>,
+    outcome: Option<Res<GameOutcome>>,
+    mut commands: Commands,
+) {
+    if !clock.is_running() || outcome.is_some() {
+        return;
+    }
+    let delta = time.delta_secs();
+    clock.spent += delta;
+    let remaining = clock.remaining_mut(*turn);
+    *remaining -= delta;
+    if *remaining <= 0. {
+        *remaining = 0.;
+        info!("{:?} ran out of time", *turn);
+        commands.insert_resource(GameOutcome::Flagged(*turn));
+    }
+}
@@ -0,0 +1,256 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::process::{Child, ChildStdin, Stdio};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Mutex, PoisonError};
+
+use bevy::prelude::*;
+
+use crate::board::{BoardState, Dimensions};
+use crate::game::{AppState, GameConfig, InGame, NewGameSet, PlayerKind};
+use crate::network::Network;
+use crate::pieces::{ChessPiece, HasMoved, PlannedMove, Team, checked_move, no_move_outcome};
+use crate::rules::GameOutcome;
+
+pub use builtin::run_engine;
+pub use protocol::{Command, EnginePosition, PlacedPiece, Reply};
+
+mod builtin;
+mod protocol;
+
+/// Sides played by an engine, a separate program the game talks to over its stdin and stdout with the protocol in `Command`.
+/// `--engine` runs the computer player as one, so it can be played against other engines.
+pub struct EnginePlugin;
+
+impl Plugin for EnginePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Engines>()
+            .add_systems(OnEnter(InGame), start_engines.in_set(NewGameSet::Configure))
+            .add_systems(OnExit(InGame), stop_engines)
+            .add_systems(Update, engine_turn.run_if(in_state(AppState::Playing)));
+    }
+}
+
+/// The running engine for each side played by one
+#[derive(Resource, Default)]
+struct Engines {
+    white: Option<EngineProcess>,
+    black: Option<EngineProcess>,
+}
+
+impl Engines {
+    fn get_mut(&mut self, team: Team) -> &mut Option<EngineProcess> {
+        match team {
+            Team::White => &mut self.white,
+            Team::Black => &mut self.black,
+        }
+    }
+}
+
+struct EngineProcess {
+    child: Child,
+    stdin: ChildStdin,
+    /// `Receiver` isn't `Sync`, only the systems holding `Engines` mutably read it so the lock is never waited on
+    lines: Mutex<Receiver<String>>,
+    /// the position sent with `go` while waiting for `bestmove`
+    asked: Option<EnginePosition>,
+}
+
+impl EngineProcess {
+    /// Run `command`, the program then its arguments, and declare the board
+    fn start(command: &[String], config: &GameConfig) -> std::io::Result<Self> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "no engine command"))?;
+        let mut child = std::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, lines) = std::sync::mpsc::channel();
+        // reading blocks, so it gets a thread of its own that ends when the engine does
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = Self {
+            child,
+            stdin,
+            lines: Mutex::new(lines),
+            asked: None,
+        };
+        for command in [
+            Command::Ndci,
+            Command::Size(config.board_size),
+            Command::Setup(config.setup),
+            Command::NewGame,
+        ] {
+            engine.send(&command)?;
+        }
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &Command) -> std::io::Result<()> {
+        writeln!(self.stdin, "{command}")?;
+        self.stdin.flush()
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        let _ = self.send(&Command::Quit);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn start_engines(config: Res<GameConfig>, network: Res<Network>, mut engines: ResMut<Engines>) {
+    for team in [Team::White, Team::Black] {
+        if network.player(&config, team) != PlayerKind::Engine {
+            continue;
+        }
+        match EngineProcess::start(&config.engine, &config) {
+            Ok(engine) => *engines.get_mut(team) = Some(engine),
+            Err(error) => error!("Couldn't start the engine for {team:?}: {error}"),
+        }
+    }
+}
+
+fn stop_engines(mut engines: ResMut<Engines>) {
+    *engines = Engines::default();
+}
+
+/// The position as the engine protocol writes it
+fn current_position(
+    turn: Team,
+    board: &BoardState,
+    pieces: &Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+) -> EnginePosition {
+    let mut placed = board
+        .iter()
+        .filter_map(|(position, entity)| {
+            let (piece, team, moved) = pieces.get(entity).ok()?;
+            Some(PlacedPiece {
+                position: position.clone(),
+                piece: *piece,
+                team: *team,
+                moved,
+            })
+        })
+        .collect::<Vec<_>>();
+    placed.sort_by(|a, b| a.position.0.cmp(&b.position.0));
+    EnginePosition {
+        turn,
+        pieces: placed,
+    }
+}
+
+/// On an engine's turn it is sent the position and asked for a move, which goes through selection like a player's.
+/// An engine that sends a move the rules here don't allow, or stops, forfeits. One with no move has been mated
+/// or stalemated, and is asked again if the board was changed while it was thinking.
+fn engine_turn(
+    config: Res<GameConfig>,
+    network: Res<Network>,
+    turn: Res<Team>,
+    dimensions: Res<Dimensions>,
+    board: Res<BoardState>,
+    pieces: Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+    mut engines: ResMut<Engines>,
+    mut planned: PlannedMove,
+    mut commands: Commands,
+) {
+    if network.player(&config, *turn) != PlayerKind::Engine {
+        planned.cancel();
+        return;
+    }
+    if planned.busy() {
+        return;
+    }
+    let team = *turn;
+    let slot = engines.get_mut(team);
+    let Some(engine) = slot else {
+        return;
+    };
+    let Some(asked) = &engine.asked else {
+        let position = current_position(team, &board, &pieces);
+        let sent = [
+            Command::Dimensions(**dimensions),
+            Command::Position {
+                start: Some(position.clone()),
+                moves: Vec::new(),
+            },
+            Command::Go,
+        ]
+        .iter()
+        .try_for_each(|command| engine.send(command));
+        match sent {
+            Ok(()) => engine.asked = Some(position),
+            Err(error) => {
+                error!("Lost the {team:?} engine: {error}");
+                *slot = None;
+                commands.insert_resource(GameOutcome::Forfeit(team));
+            }
+        }
+        return;
+    };
+    loop {
+        let line = match engine
+            .lines
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .try_recv()
+        {
+            Ok(line) => line,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                error!("The {team:?} engine stopped");
+                *slot = None;
+                commands.insert_resource(GameOutcome::Forfeit(team));
+                return;
+            }
+        };
+        let step = match line.parse::<Reply>() {
+            Ok(Reply::BestMove(step)) => step,
+            Ok(Reply::Info(text)) => {
+                info!("{team:?} engine: {text}");
+                continue;
+            }
+            Ok(Reply::Id(name)) => {
+                info!("{team:?} is played by {name}");
+                continue;
+            }
+            Ok(Reply::NdciOk | Reply::ReadyOk) => continue,
+            // engines can send more then this protocol knows about
+            Err(error) => {
+                debug!("{team:?} engine: {error}");
+                continue;
+            }
+        };
+        // `=` and `-` change the board under it, the answer is for a position that is gone
+        if *asked != current_position(team, &board, &pieces) {
+            engine.asked = None;
+            return;
+        }
+        engine.asked = None;
+        let Some(step) = step else {
+            let outcome = no_move_outcome(team, &board, &pieces);
+            if outcome.is_none() {
+                warn!("The {team:?} engine has no move but there is one");
+            }
+            commands.insert_resource(outcome.unwrap_or(GameOutcome::Forfeit(team)));
+            return;
+        };
+        match checked_move(&step.from, &step.to, team, &board, &pieces) {
+            Ok((piece, ..)) => planned.start(piece, step.to),
+            Err(error) => {
+                warn!("The {team:?} engine sent {step}: {error}");
+                commands.insert_resource(GameOutcome::Forfeit(team));
+            }
+        }
+        return;
+    }
+}
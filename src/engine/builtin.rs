@@ -0,0 +1,208 @@
+use std::io::{BufRead, Write};
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+
+use super::protocol::{Command, EngineMove, EnginePosition, PlacedPiece, Reply};
+use crate::board::{BoardState, MAX_BOARD_SIZE, MIN_BOARD_SIZE, Position};
+use crate::game::GameConfig;
+use crate::pieces::{
+    ChessPiece, HasMoved, StartingSetup, Team, checked_move, choose_move, play_move,
+    starting_pieces,
+};
+use crate::rules::position_key;
+
+/// Answer the engine protocol on stdin and stdout with the computer player's moves,
+/// so it can play other engines or be run by the game like any other engine
+pub fn run_engine() -> AppExit {
+    let mut engine = BuiltinEngine::default();
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let replies = match line.parse::<Command>() {
+            Ok(Command::Quit) => break,
+            Ok(command) => engine.handle(command),
+            Err(error) => vec![Reply::Info(error)],
+        };
+        for reply in replies {
+            if writeln!(stdout, "{reply}").is_err() {
+                return AppExit::error();
+            }
+        }
+        if stdout.flush().is_err() {
+            return AppExit::error();
+        }
+    }
+    AppExit::Success
+}
+
+/// The position is kept as pieces in a world of its own so the game's move rules can run on it
+struct BuiltinEngine {
+    dimensions: usize,
+    size: usize,
+    setup: StartingSetup,
+    world: World,
+}
+
+impl Default for BuiltinEngine {
+    fn default() -> Self {
+        let config = GameConfig::default();
+        let mut engine = Self {
+            dimensions: config.dimensions,
+            size: config.board_size,
+            setup: StartingSetup::default(),
+            world: World::new(),
+        };
+        engine
+            .set_position(None, &[])
+            .expect("The starting position is always legal");
+        engine
+    }
+}
+
+impl BuiltinEngine {
+    fn handle(&mut self, command: Command) -> Vec<Reply> {
+        let result = match command {
+            Command::Ndci => {
+                return vec![
+                    Reply::Id(format!("nd_chess {}", env!("CARGO_PKG_VERSION"))),
+                    Reply::NdciOk,
+                ];
+            }
+            Command::IsReady => return vec![Reply::ReadyOk],
+            Command::Go => return vec![Reply::BestMove(self.best_move())],
+            Command::NewGame | Command::Quit => Ok(()),
+            Command::Dimensions(dimensions) => {
+                let range = GameConfig::MIN_DIMENSIONS..=GameConfig::MAX_DIMENSIONS;
+                if range.contains(&dimensions) {
+                    self.dimensions = dimensions;
+                    self.set_position(None, &[])
+                } else {
+                    Err(format!("can't play on {dimensions} dimensions"))
+                }
+            }
+            Command::Size(size) => {
+                if (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size) {
+                    self.size = size;
+                    self.set_position(None, &[])
+                } else {
+                    Err(format!(
+                        "only boards of size {MIN_BOARD_SIZE} to {MAX_BOARD_SIZE} are supported, not {size}"
+                    ))
+                }
+            }
+            Command::Setup(setup) => {
+                self.setup = setup;
+                Ok(())
+            }
+            Command::Position { start, moves } => self.set_position(start, &moves),
+        };
+        match result {
+            Ok(()) => Vec::new(),
+            Err(error) => vec![Reply::Info(error)],
+        }
+    }
+
+    /// Set up `start`, or the starting position when it is `None`, and play `moves` on it.
+    /// Stops at the first illegal move, the position is left as it was before that move.
+    fn set_position(
+        &mut self,
+        start: Option<EnginePosition>,
+        moves: &[EngineMove],
+    ) -> Result<(), String> {
+        let (turn, pieces) = match start {
+            Some(start) => (start.turn, start.pieces),
+            None => (
+                Team::White,
+                starting_pieces(self.dimensions, self.size as i8, self.setup)
+                    .map(|(position, piece, team)| PlacedPiece {
+                        position,
+                        piece,
+                        team,
+                        moved: false,
+                    })
+                    .collect(),
+            ),
+        };
+        let mut world = World::new();
+        world.insert_resource(BoardState::new(self.size));
+        world.insert_resource(turn);
+        for placed in pieces {
+            if placed.position.len() != self.dimensions
+                || !placed.position.is_valid(self.size as i8)
+            {
+                return Err(format!(
+                    "{} isn't a cell on a {} dimensional board of size {}",
+                    placed.position, self.dimensions, self.size
+                ));
+            }
+            let mut piece = world.spawn((placed.position, placed.piece, placed.team));
+            if placed.moved {
+                piece.insert(HasMoved);
+            }
+        }
+        self.world = world;
+        for step in moves {
+            self.world
+                .run_system_once_with(play_engine_move, step.clone())
+                .map_err(|error| error.to_string())??;
+        }
+        Ok(())
+    }
+
+    fn best_move(&mut self) -> Option<EngineMove> {
+        self.world.run_system_once(pick_engine_move).ok().flatten()
+    }
+}
+
+/// Moves are checked and played the same way the server plays them
+fn play_engine_move(
+    In(step): In<EngineMove>,
+    mut board: ResMut<BoardState>,
+    mut turn: ResMut<Team>,
+    mut positions: Query<&mut Position, With<ChessPiece>>,
+    info: Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+    pieces: Query<(&ChessPiece, &Team)>,
+    mut commands: Commands,
+) -> Result<(), String> {
+    let (entity, piece, kind) = checked_move(&step.from, &step.to, *turn, &board, &info)?;
+    let mut position = positions
+        .get_mut(entity)
+        .map_err(|error| error.to_string())?;
+    play_move(
+        (entity, piece, *turn),
+        &mut position,
+        &step.to,
+        &kind,
+        &mut board,
+        &pieces,
+        &mut commands,
+    );
+    if let Some(captured) = board.take_captured() {
+        commands.entity(captured).despawn();
+    }
+    *turn = turn.opposite();
+    Ok(())
+}
+
+/// Positions usually arrive without the moves that led to them, so the position itself picks between equal moves
+fn pick_engine_move(
+    turn: Res<Team>,
+    board: Res<BoardState>,
+    pieces: Query<(Entity, &ChessPiece, &Team, &Position)>,
+    info: Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+    keys: Query<(&ChessPiece, &Team)>,
+) -> Option<EngineMove> {
+    let variation = position_key(&board, &keys, *turn) as usize;
+    let (entity, to) = choose_move(*turn, &board, &pieces, &info, variation)?;
+    let (.., from) = pieces.get(entity).ok()?;
+    Some(EngineMove {
+        from: from.clone(),
+        to,
+    })
+}
@@ -0,0 +1,463 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use crate::board::Position;
+use crate::pieces::{ChessPiece, StartingSetup, Team};
+
+/// What the game sends an engine, one command a line.
+/// Modelled on UCI, but the board has to be declared before a position since it can have any number of axes:
+///
+/// - `ndci` the engine answers with `id name <name>` then `ndciok`
+/// - `isready` answered with `readyok` once everything sent before it has been handled
+/// - `ndcinewgame` the next position is from a different game
+/// - `dimensions <n>` and `size <n>` declare the board, `size` is the cells along every axis
+/// - `setup every` or `setup <axes>` which boards `startpos` puts pieces on, see `StartingSetup`
+/// - `position startpos [moves ...]` or `position fen <position> [moves ...]`, see `EnginePosition`
+/// - `go` answered with `bestmove <move>` for the side to move, or `bestmove (none)`
+/// - `quit`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Ndci,
+    IsReady,
+    NewGame,
+    Dimensions(usize),
+    Size(usize),
+    Setup(StartingSetup),
+    Position {
+        /// `None` is the starting position
+        start: Option<EnginePosition>,
+        moves: Vec<EngineMove>,
+    },
+    Go,
+    Quit,
+}
+
+/// What an engine sends back
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// `id name <name>`
+    Id(String),
+    NdciOk,
+    ReadyOk,
+    BestMove(Option<EngineMove>),
+    /// `info string <text>`, anything the engine wants to say, errors included
+    Info(String),
+}
+
+/// A move as the two cells, `e2.1e4.1`, a pawn reaching the last rank always becomes a queen
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineMove {
+    pub from: Position,
+    pub to: Position,
+}
+
+/// A whole position on one line, like FEN but listing pieces by cell as an N-D board can't be written rank by rank.
+/// The side to move, `w` or `b`, then every piece as its letter and cell, upper case for white and lower case for black.
+/// A piece followed by `*` has moved, so can't castle: `w Ke1.1 Ra1.1 Pe3.1* ke8.1 ra8.1`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnginePosition {
+    pub turn: Team,
+    pub pieces: Vec<PlacedPiece>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedPiece {
+    pub position: Position,
+    pub piece: ChessPiece,
+    pub team: Team,
+    pub moved: bool,
+}
+
+/// Unlike `ChessPiece::letter` pawns get a letter too
+fn symbol(piece: ChessPiece, team: Team) -> char {
+    let letter = match piece {
+        ChessPiece::Pawn => 'P',
+        ChessPiece::Rook => 'R',
+        ChessPiece::Knight => 'N',
+        ChessPiece::Bishop => 'B',
+        ChessPiece::Queen => 'Q',
+        ChessPiece::King => 'K',
+    };
+    match team {
+        Team::White => letter,
+        Team::Black => letter.to_ascii_lowercase(),
+    }
+}
+
+fn from_symbol(symbol: char) -> Option<(ChessPiece, Team)> {
+    let piece = match symbol.to_ascii_uppercase() {
+        'P' => ChessPiece::Pawn,
+        'R' => ChessPiece::Rook,
+        'N' => ChessPiece::Knight,
+        'B' => ChessPiece::Bishop,
+        'Q' => ChessPiece::Queen,
+        'K' => ChessPiece::King,
+        _ => return None,
+    };
+    let team = if symbol.is_ascii_uppercase() {
+        Team::White
+    } else {
+        Team::Black
+    };
+    Some((piece, team))
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Ndci => write!(f, "ndci"),
+            Command::IsReady => write!(f, "isready"),
+            Command::NewGame => write!(f, "ndcinewgame"),
+            Command::Dimensions(dimensions) => write!(f, "dimensions {dimensions}"),
+            Command::Size(size) => write!(f, "size {size}"),
+            Command::Setup(StartingSetup::EverySlice) => write!(f, "setup every"),
+            Command::Setup(StartingSetup::FirstAxes { axes }) => write!(f, "setup {axes}"),
+            Command::Position { start, moves } => {
+                match start {
+                    Some(start) => write!(f, "position fen {start}")?,
+                    None => write!(f, "position startpos")?,
+                }
+                if !moves.is_empty() {
+                    write!(f, " moves")?;
+                    for step in moves {
+                        write!(f, " {step}")?;
+                    }
+                }
+                Ok(())
+            }
+            Command::Go => write!(f, "go"),
+            Command::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let number = |word: Option<&str>| {
+            word.and_then(|word| word.parse::<usize>().ok())
+                .ok_or_else(|| format!("{line:?} needs a number"))
+        };
+        let command = match words.next() {
+            Some("ndci") => Command::Ndci,
+            Some("isready") => Command::IsReady,
+            Some("ndcinewgame") => Command::NewGame,
+            Some("dimensions") => Command::Dimensions(number(words.next())?),
+            Some("size") => Command::Size(number(words.next())?),
+            Some("setup") => match words.next() {
+                Some("every") => Command::Setup(StartingSetup::EverySlice),
+                axes => Command::Setup(StartingSetup::FirstAxes {
+                    axes: number(axes)?,
+                }),
+            },
+            Some("position") => {
+                let rest = words.collect::<Vec<_>>();
+                let (start, moves) = match rest.iter().position(|&word| word == "moves") {
+                    Some(split) => (&rest[..split], &rest[split + 1..]),
+                    None => (&rest[..], &[][..]),
+                };
+                let start = match start.split_first() {
+                    Some((&"startpos", [])) => None,
+                    Some((&"fen", fen)) => Some(fen.join(" ").parse()?),
+                    _ => return Err(format!("{line:?} needs startpos or fen <position>")),
+                };
+                let moves = moves
+                    .iter()
+                    .map(|step| step.parse())
+                    .collect::<Result<_, _>>()?;
+                Command::Position { start, moves }
+            }
+            Some("go") => Command::Go,
+            Some("quit") => Command::Quit,
+            _ => return Err(format!("unknown command {line:?}")),
+        };
+        Ok(command)
+    }
+}
+
+impl Display for Reply {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Reply::Id(name) => write!(f, "id name {name}"),
+            Reply::NdciOk => write!(f, "ndciok"),
+            Reply::ReadyOk => write!(f, "readyok"),
+            Reply::BestMove(Some(step)) => write!(f, "bestmove {step}"),
+            Reply::BestMove(None) => write!(f, "bestmove (none)"),
+            Reply::Info(text) => write!(f, "info string {text}"),
+        }
+    }
+}
+
+impl FromStr for Reply {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let reply = if let Some(name) = line.strip_prefix("id name ") {
+            Reply::Id(name.to_string())
+        } else if line == "ndciok" {
+            Reply::NdciOk
+        } else if line == "readyok" {
+            Reply::ReadyOk
+        } else if line == "bestmove (none)" {
+            Reply::BestMove(None)
+        } else if let Some(step) = line.strip_prefix("bestmove ") {
+            // like UCI anything after the move is ignored
+            let step = step.split_whitespace().next().unwrap_or_default();
+            Reply::BestMove(Some(step.parse()?))
+        } else if let Some(text) = line.strip_prefix("info string ") {
+            Reply::Info(text.to_string())
+        } else {
+            return Err(format!("unknown reply {line:?}"));
+        };
+        Ok(reply)
+    }
+}
+
+impl Display for EngineMove {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.from, self.to)
+    }
+}
+
+impl FromStr for EngineMove {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        // every cell starts with its file letter
+        let split = text
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| c.is_ascii_alphabetic())
+            .map(|(i, _)| i)
+            .ok_or_else(|| format!("{text} isn't two cells"))?;
+        Ok(EngineMove {
+            from: text[..split].parse()?,
+            to: text[split..].parse()?,
+        })
+    }
+}
+
+impl Display for EnginePosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let turn = match self.turn {
+            Team::White => 'w',
+            Team::Black => 'b',
+        };
+        write!(f, "{turn}")?;
+        for placed in &self.pieces {
+            write!(
+                f,
+                " {}{}",
+                symbol(placed.piece, placed.team),
+                placed.position
+            )?;
+            if placed.moved {
+                write!(f, "*")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for EnginePosition {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut words = text.split_whitespace();
+        let turn = match words.next() {
+            Some("w") => Team::White,
+            Some("b") => Team::Black,
+            _ => return Err(format!("{text:?} doesn't start with the side to move")),
+        };
+        let pieces = words
+            .map(|word| {
+                let mut chars = word.chars();
+                let (piece, team) = chars
+                    .next()
+                    .and_then(from_symbol)
+                    .ok_or_else(|| format!("{word} doesn't start with a piece"))?;
+                let cell = chars.as_str();
+                let (cell, moved) = match cell.strip_suffix('*') {
+                    Some(cell) => (cell, true),
+                    None => (cell, false),
+                };
+                Ok(PlacedPiece {
+                    position: cell.parse()?,
+                    piece,
+                    team,
+                    moved,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(EnginePosition { turn, pieces })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(text: &str) -> Position {
+        text.parse().unwrap()
+    }
+
+    fn sample_position() -> EnginePosition {
+        EnginePosition {
+            turn: Team::Black,
+            pieces: vec![
+                PlacedPiece {
+                    position: cell("e1.1"),
+                    piece: ChessPiece::King,
+                    team: Team::White,
+                    moved: false,
+                },
+                PlacedPiece {
+                    position: cell("e3.2"),
+                    piece: ChessPiece::Pawn,
+                    team: Team::White,
+                    moved: true,
+                },
+                PlacedPiece {
+                    position: cell("h8.8"),
+                    piece: ChessPiece::Knight,
+                    team: Team::Black,
+                    moved: false,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let step = EngineMove {
+            from: cell("e2.1"),
+            to: cell("e4.1"),
+        };
+        for command in [
+            Command::Ndci,
+            Command::IsReady,
+            Command::NewGame,
+            Command::Dimensions(3),
+            Command::Size(8),
+            Command::Setup(StartingSetup::EverySlice),
+            Command::Setup(StartingSetup::FirstAxes { axes: 4 }),
+            Command::Position {
+                start: None,
+                moves: Vec::new(),
+            },
+            Command::Position {
+                start: None,
+                moves: vec![step.clone(), step.clone()],
+            },
+            Command::Position {
+                start: Some(sample_position()),
+                moves: vec![step],
+            },
+            Command::Go,
+            Command::Quit,
+        ] {
+            assert_eq!(command.to_string().parse(), Ok(command));
+        }
+    }
+
+    #[test]
+    fn malformed_commands_are_errors() {
+        for line in [
+            "",
+            "hello",
+            "dimensions",
+            "dimensions three",
+            "size -1",
+            "setup",
+            "setup some",
+            "position",
+            "position startpos extra",
+            "position fen",
+            "position fen x Ke1.1",
+            "position fen w Ke-128",
+            "position startpos moves e2",
+            "position startpos moves e2.1e11.1",
+        ] {
+            assert!(line.parse::<Command>().is_err(), "{line:?} parsed");
+        }
+    }
+
+    #[test]
+    fn replies_round_trip() {
+        for reply in [
+            Reply::Id(String::from("nd_chess 0.1.0")),
+            Reply::NdciOk,
+            Reply::ReadyOk,
+            Reply::BestMove(None),
+            Reply::BestMove(Some(EngineMove {
+                from: cell("b1.3"),
+                to: cell("c3.3"),
+            })),
+            Reply::Info(String::from("only boards of size 8 are supported")),
+        ] {
+            assert_eq!(reply.to_string().parse(), Ok(reply));
+        }
+        assert_eq!(
+            "bestmove e2e4 ponder e7e5".parse(),
+            Ok(Reply::BestMove(Some(EngineMove {
+                from: cell("e2"),
+                to: cell("e4"),
+            })))
+        );
+    }
+
+    #[test]
+    fn malformed_replies_are_errors() {
+        for line in [
+            "",
+            "hello",
+            "id",
+            "bestmove",
+            "bestmove e2",
+            "bestmove e2e-128",
+        ] {
+            assert!(line.parse::<Reply>().is_err(), "{line:?} parsed");
+        }
+    }
+
+    #[test]
+    fn moves_round_trip() {
+        let step = EngineMove {
+            from: cell("a1.1.8"),
+            to: cell("h8.8.1"),
+        };
+        assert_eq!(step.to_string(), "a1.1.8h8.8.1");
+        assert_eq!(step.to_string().parse(), Ok(step));
+    }
+
+    #[test]
+    fn malformed_moves_are_errors() {
+        for text in ["", "e2", "e2e", "22", "e2e11", "e2.1e4.x", "e0e4", "E2E4"] {
+            assert!(text.parse::<EngineMove>().is_err(), "{text:?} parsed");
+        }
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        let position = sample_position();
+        assert_eq!(position.to_string(), "b Ke1.1 Pe3.2* nh8.8");
+        assert_eq!(position.to_string().parse(), Ok(position));
+        let empty = EnginePosition {
+            turn: Team::White,
+            pieces: Vec::new(),
+        };
+        assert_eq!("w".parse(), Ok(empty));
+    }
+
+    #[test]
+    fn malformed_positions_are_errors() {
+        for text in [
+            "", "x", "x Ke1", "w Xe1", "w K", "w Ke1**", "w Ke-128", "w Ke11",
+        ] {
+            assert!(text.parse::<EnginePosition>().is_err(), "{text:?} parsed");
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use bevy::prelude::*;
+
+use crate::board::{BoardState, Dimensions, Position, RenderedAxes};
+use crate::clock::TimeControl;
+use crate::network::NetworkRole;
+use crate::pieces::{ChessPiece, StartingSetup, Team};
+use crate::rules::{DrawRules, GameOutcome, MoveHistory};
+
+/// Where the app is, the board and pieces only exist while `InGame`
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(GameStatePlugin)
+            .add_systems(OnEnter(AppState::GameOver), spawn_game_over)
+            .add_systems(
+                Update,
+                (
+                    new_game_controls.run_if(in_state(InGame)),
+                    (dimension_controls, change_dimensions)
+                        .chain()
+                        .run_if(in_state(AppState::Playing)),
+                ),
+            );
+    }
+}
+
+/// The states a game goes through and setting up its resources, without anything shown or any input,
+/// so the headless server runs games the same way
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .add_computed_state::<InGame>()
+            .enable_state_scoped_entities::<InGame>()
+            .init_resource::<GameConfig>()
+            .configure_sets(
+                OnEnter(InGame),
+                (
+                    NewGameSet::Configure,
+                    NewGameSet::Spawn,
+                    NewGameSet::Record,
+                    NewGameSet::Replay,
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(InGame), start_game.in_set(NewGameSet::Configure))
+            .add_systems(OnEnter(AppState::NewGame), play_new_game)
+            .add_systems(
+                Update,
+                finish_game.run_if(in_state(AppState::Playing).and(resource_exists::<GameOutcome>)),
+            );
+    }
+}
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[states(scoped_entities)]
+pub enum AppState {
+    /// choosing the options for the next game, the server waits here for two players
+    #[default]
+    Menu,
+    Playing,
+    /// the board is still shown but no more moves can be made
+    GameOver,
+    /// the last game has been cleared away, goes straight on to `Playing` with the same config
+    NewGame,
+}
+
+/// Playing or looking at the finished game, the board and HUD are scoped to this
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InGame;
+
+impl ComputedStates for InGame {
+    type SourceStates = AppState;
+
+    fn compute(state: AppState) -> Option<Self> {
+        matches!(state, AppState::Playing | AppState::GameOver).then_some(InGame)
+    }
+}
+
+/// The order things are set up in when a game starts
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NewGameSet {
+    /// copy the `GameConfig` into the resources the game runs from
+    Configure,
+    /// the board and the pieces
+    Spawn,
+    /// anything that needs the pieces on the board, like the draw rules' first position
+    Record,
+    /// moves played before this end joined the game, played on from the recorded start
+    Replay,
+}
+
+/// Who makes the moves for a side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlayerKind {
+    #[default]
+    Human,
+    Computer,
+    /// moves arrive over the network
+    Remote,
+    /// moves come from running `GameConfig::engine`
+    Engine,
+}
+
+/// Everything picked in the menu for the next game
+#[derive(Resource, Debug, Clone)]
+pub struct GameConfig {
+    pub dimensions: usize,
+    /// cells along every axis
+    pub board_size: usize,
+    pub setup: StartingSetup,
+    pub draw_rules: DrawRules,
+    pub white: PlayerKind,
+    pub black: PlayerKind,
+    /// either side can be moved at any time rather then taking turns, only between people at one screen
+    pub any_order: bool,
+    /// changing `Dimensions` mid game keeps the pieces, placed on slice 0 of any new axis,
+    /// rather then starting a new game
+    pub lift_position: bool,
+    pub time_control: TimeControl,
+    pub network: NetworkRole,
+    /// the program and its arguments run for a side played by `PlayerKind::Engine`
+    pub engine: Vec<String>,
+}
+
+impl GameConfig {
+    pub const MIN_DIMENSIONS: usize = 2;
+    pub const MAX_DIMENSIONS: usize = 8;
+
+    pub fn player(&self, team: Team) -> PlayerKind {
+        match team {
+            Team::White => self.white,
+            Team::Black => self.black,
+        }
+    }
+
+    /// Whether `any_order` applies, computer, engine and networked players always wait for their turn
+    pub fn plays_in_any_order(&self) -> bool {
+        self.any_order
+            && self.network == NetworkRole::Offline
+            && self.white == PlayerKind::Human
+            && self.black == PlayerKind::Human
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            dimensions: 5,
+            board_size: 8,
+            setup: StartingSetup::default(),
+            draw_rules: DrawRules::default(),
+            white: PlayerKind::Human,
+            black: PlayerKind::Human,
+            any_order: false,
+            lift_position: true,
+            time_control: TimeControl::default(),
+            network: NetworkRole::default(),
+            // the computer player, run as an engine by this same program
+            engine: vec![
+                std::env::current_exe().map_or_else(
+                    |_| String::from("nd_chess"),
+                    |exe| exe.display().to_string(),
+                ),
+                String::from("--engine"),
+            ],
+        }
+    }
+}
+
+/// Set up the resources for a new game from the config, the board and pieces are spawned after this
+fn start_game(
+    config: Res<GameConfig>,
+    mut dimensions: ResMut<Dimensions>,
+    mut axes: ResMut<RenderedAxes>,
+    mut setup: ResMut<StartingSetup>,
+    mut draw_rules: ResMut<DrawRules>,
+    mut board: ResMut<BoardState>,
+    mut history: ResMut<MoveHistory>,
+    mut turn: ResMut<Team>,
+    mut commands: Commands,
+) {
+    **dimensions = config.dimensions;
+    let layout = axes.layout;
+    *board = BoardState::new(config.board_size);
+    *axes = RenderedAxes::all(config.dimensions, board.size());
+    axes.layout = layout;
+    *setup = config.setup;
+    *draw_rules = config.draw_rules.clone();
+    *history = MoveHistory::default();
+    *turn = Team::White;
+    commands.remove_resource::<GameOutcome>();
+}
+
+fn finish_game(mut state: ResMut<NextState<AppState>>) {
+    state.set(AppState::GameOver);
+}
+
+/// F5 starts a new game with the same options, F6 goes back to the menu to pick new ones.
+/// Leaving `InGame` despawns the board and pieces so the next game starts from nothing.
+/// Only the host of a networked game can start a new one, which is sent to the other player.
+fn new_game_controls(
+    input: Res<ButtonInput<KeyCode>>,
+    config: Res<GameConfig>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    if input.just_pressed(KeyCode::F5) && config.network.picks_game() {
+        state.set(AppState::NewGame);
+    } else if input.just_pressed(KeyCode::F6) {
+        state.set(AppState::Menu);
+    }
+}
+
+/// = adds an axis and - takes the last one away, only the host of a networked game can change it
+fn dimension_controls(
+    input: Res<ButtonInput<KeyCode>>,
+    config: Res<GameConfig>,
+    mut dimensions: ResMut<Dimensions>,
+) {
+    if !config.network.picks_game() {
+        return;
+    }
+    if input.just_pressed(KeyCode::Equal) {
+        **dimensions += 1;
+    }
+    if input.just_pressed(KeyCode::Minus) {
+        **dimensions -= 1;
+    }
+}
+
+/// The pieces are moved onto the new board when `Dimensions` changes, or a new game is started.
+/// A networked game always starts a new one, which the host sends to the other player like any new game.
+fn change_dimensions(
+    mut dimensions: ResMut<Dimensions>,
+    mut config: ResMut<GameConfig>,
+    mut axes: ResMut<RenderedAxes>,
+    pieces: Query<(&ChessPiece, &Position)>,
+    mut state: ResMut<NextState<AppState>>,
+    mut commands: Commands,
+) {
+    // the rendered axes always match the board being played on
+    let playing = axes.dimensions();
+    if !dimensions.is_changed() || **dimensions == playing {
+        return;
+    }
+    let range = GameConfig::MIN_DIMENSIONS..=GameConfig::MAX_DIMENSIONS;
+    if !range.contains(&**dimensions) {
+        warn!(
+            "Can't play on {} dimensions, staying on {playing}",
+            **dimensions
+        );
+        **dimensions = playing;
+        return;
+    }
+    let lift = config.lift_position && config.network == NetworkRole::Offline;
+    // a king off slice 0 of a removed axis would be taken off, leaving a game that can't be won
+    if lift
+        && let Some(king) = pieces.iter().find(|(piece, position)| {
+            **piece == ChessPiece::King && position.iter().skip(**dimensions).any(|&i| i != 0)
+        })
+    {
+        warn!(
+            "Can't take away the last axis with a king on {}, staying on {playing} dimensions",
+            king.1
+        );
+        **dimensions = playing;
+        return;
+    }
+    info!("Changing from {playing} to {} dimensions", **dimensions);
+    config.dimensions = **dimensions;
+    if !lift {
+        state.set(AppState::NewGame);
+        return;
+    }
+    let layout = axes.layout;
+    *axes = RenderedAxes::all(**dimensions, axes.size);
+    axes.layout = layout;
+    commands.trigger(LiftPosition {
+        dimensions: **dimensions,
+    });
+}
+
+/// Move every piece onto a board with `dimensions` axes,
+/// new axes start at slice 0 and pieces off slice 0 of a removed axis are taken off
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LiftPosition {
+    pub dimensions: usize,
+}
+
+fn play_new_game(mut state: ResMut<NextState<AppState>>) {
+    state.set(AppState::Playing);
+}
+
+fn spawn_game_over(outcome: Option<Res<GameOutcome>>, mut commands: Commands) {
+    let message = match outcome.as_deref() {
+        Some(GameOutcome::Draw(reason)) => format!("Game drawn: {reason:?}"),
+        Some(GameOutcome::Flagged(team)) => {
+            format!("{team:?} ran out of time, {:?} wins", team.opposite())
+        }
+        Some(GameOutcome::Checkmate(team)) => {
+            format!("{team:?} is checkmated, {:?} wins", team.opposite())
+        }
+        Some(GameOutcome::Forfeit(team)) => {
+            format!("{team:?}'s engine failed, {:?} wins", team.opposite())
+        }
+        None => String::from("Game over"),
+    } + "\nF5 to play again, F6 for the menu";
+    commands
+        .spawn((
+            Name::new("Game Over"),
+            StateScoped(AppState::GameOver),
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            Pickable::IGNORE,
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                Node {
+                    padding: UiRect::all(Val::Px(16.)),
+                    ..Default::default()
+                },
+                BackgroundColor(Color::BLACK.with_alpha(0.7)),
+                Pickable::IGNORE,
+                children![(
+                    Text::new(message),
+                    TextFont {
+                        font_size: 32.,
+                        ..Default::default()
+                    },
+                    Pickable::IGNORE,
+                )],
+            ));
+        });
+}
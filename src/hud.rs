@@ -0,0 +1,224 @@
+use bevy::{prelude::*, render::view::RenderLayers};
+
+use crate::board::Position;
+use crate::clock::{ChessClock, format_time};
+use crate::game::InGame;
+use crate::pieces::{ChessPiece, Selected, Team};
+use crate::rules::{GameOutcome, MoveHistory};
+
+/// Text over the board showing the state of the game
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_hud_camera)
+            .add_systems(OnEnter(InGame), spawn_hud)
+            .add_systems(
+                Update,
+                (
+                    update_turn,
+                    update_clock,
+                    update_captured,
+                    update_selected,
+                    update_moves,
+                )
+                    .run_if(in_state(InGame)),
+            );
+    }
+}
+
+/// How many lines of the move list are shown, older moves scroll off the top
+const MOVE_LINES: usize = 24;
+
+#[derive(Component)]
+struct TurnText;
+
+#[derive(Component)]
+struct ClockText;
+
+#[derive(Component)]
+struct CapturedText;
+
+#[derive(Component)]
+struct SelectedText;
+
+#[derive(Component)]
+struct MoveListText;
+
+/// The menus use this camera too
+fn spawn_hud_camera(mut commands: Commands) {
+    // the split view gives the board cameras viewports, the HUD is drawn over the whole window by its own camera
+    commands.spawn((
+        Name::new("HUD Camera"),
+        Camera2d,
+        Camera {
+            order: 100,
+            clear_color: ClearColorConfig::None,
+            ..Default::default()
+        },
+        IsDefaultUiCamera,
+        RenderLayers::none(),
+    ));
+}
+
+fn spawn_hud(mut commands: Commands) {
+    let font = TextFont {
+        font_size: 18.,
+        ..Default::default()
+    };
+    let panel = Node {
+        flex_direction: FlexDirection::Column,
+        row_gap: Val::Px(4.),
+        padding: UiRect::all(Val::Px(8.)),
+        ..Default::default()
+    };
+    let background = BackgroundColor(Color::BLACK.with_alpha(0.5));
+    commands
+        .spawn((
+            Name::new("HUD"),
+            StateScoped(InGame),
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::SpaceBetween,
+                align_items: AlignItems::FlexStart,
+                padding: UiRect::all(Val::Px(8.)),
+                ..Default::default()
+            },
+            // clicks go through to the board
+            Pickable::IGNORE,
+        ))
+        .with_children(|hud| {
+            hud.spawn((panel.clone(), background, Pickable::IGNORE))
+                .with_children(|status| {
+                    status.spawn((TurnText, Text::default(), font.clone(), Pickable::IGNORE));
+                    status.spawn((ClockText, Text::default(), font.clone(), Pickable::IGNORE));
+                    status.spawn((
+                        CapturedText,
+                        Text::default(),
+                        font.clone(),
+                        Pickable::IGNORE,
+                    ));
+                    status.spawn((
+                        SelectedText,
+                        Text::default(),
+                        font.clone(),
+                        Pickable::IGNORE,
+                    ));
+                });
+            hud.spawn((panel, background, Pickable::IGNORE))
+                .with_children(|moves| {
+                    moves.spawn((MoveListText, Text::default(), font, Pickable::IGNORE));
+                });
+        });
+}
+
+/// Only touch the text when it says something new, setting it relayouts the HUD
+fn set_text(text: &mut Text, value: String) {
+    if text.0 != value {
+        text.0 = value;
+    }
+}
+
+fn update_turn(
+    turn: Res<Team>,
+    outcome: Option<Res<GameOutcome>>,
+    mut text: Single<&mut Text, With<TurnText>>,
+) {
+    let value = match outcome.as_deref() {
+        Some(GameOutcome::Draw(reason)) => format!("Draw: {reason:?}"),
+        Some(GameOutcome::Flagged(team)) => format!("{:?} wins on time", team.opposite()),
+        Some(GameOutcome::Checkmate(team)) => format!("{:?} wins by checkmate", team.opposite()),
+        Some(GameOutcome::Forfeit(team)) => format!("{:?} wins by forfeit", team.opposite()),
+        None => format!("{:?} to move", *turn),
+    };
+    set_text(&mut text, value);
+}
+
+/// Both clocks, the side to move is marked. Empty when the game has no clock.
+fn update_clock(
+    clock: Res<ChessClock>,
+    turn: Res<Team>,
+    mut text: Single<&mut Text, With<ClockText>>,
+) {
+    if !clock.is_running() {
+        set_text(&mut text, String::new());
+        return;
+    }
+    let side = |team: Team| {
+        let marker = if team == *turn { ">" } else { " " };
+        format!("{marker} {team:?} {}", format_time(clock.remaining(team)))
+    };
+    set_text(
+        &mut text,
+        format!("{}\n{}", side(Team::White), side(Team::Black)),
+    );
+}
+
+/// The material each side has taken, in the values used to weigh exchanges
+fn update_captured(history: Res<MoveHistory>, mut text: Single<&mut Text, With<CapturedText>>) {
+    if !history.is_changed() {
+        return;
+    }
+    let side = |team: Team| {
+        let taken = history
+            .moves
+            .iter()
+            .filter(|made| made.team == team)
+            .filter_map(|made| made.captured)
+            .collect::<Vec<ChessPiece>>();
+        let value = taken.iter().map(ChessPiece::value).sum::<i32>();
+        let names = taken
+            .iter()
+            .map(|piece| format!("{piece:?}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{team:?} took {value} ({names})")
+    };
+    set_text(
+        &mut text,
+        format!("{}\n{}", side(Team::White), side(Team::Black)),
+    );
+}
+
+fn update_selected(
+    selected: Query<(&ChessPiece, &Team, &Position), With<Selected>>,
+    mut text: Single<&mut Text, With<SelectedText>>,
+) {
+    let value = match selected.iter().next() {
+        Some((piece, team, position)) => {
+            format!("{team:?} {piece:?} on {position} {:?}", position.0)
+        }
+        None => String::from("Nothing selected"),
+    };
+    set_text(&mut text, value);
+}
+
+/// Numbered like a score sheet, white's move then black's reply
+fn update_moves(history: Res<MoveHistory>, mut text: Single<&mut Text, With<MoveListText>>) {
+    if !history.is_changed() {
+        return;
+    }
+    let lines = history
+        .moves
+        .chunks(2)
+        .enumerate()
+        .map(|(number, pair)| {
+            let pair = pair
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("  ");
+            format!("{}. {pair}", number + 1)
+        })
+        .collect::<Vec<_>>();
+    let shown = &lines[lines.len().saturating_sub(MOVE_LINES)..];
+    set_text(
+        &mut text,
+        if shown.is_empty() {
+            String::from("No moves yet")
+        } else {
+            shown.join("\n")
+        },
+    );
+}
@@ -5,13 +5,54 @@ mod board;
 
 mod camera;
 
+mod clock;
+
+mod engine;
+
+mod game;
+
+mod hud;
+
+mod menu;
+
+mod network;
+
 mod pieces;
 
-fn main() {
+mod rules;
+
+fn main() -> AppExit {
+    let mut config = game::GameConfig::default();
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        // runs a headless game for others to join instead of opening a window
+        Some("--server") => {
+            let port = args
+                .next()
+                .and_then(|port| port.parse().ok())
+                .unwrap_or(network::NetworkRole::DEFAULT_PORT);
+            return network::run_server(port, config);
+        }
+        // the computer player speaking the engine protocol on stdin and stdout
+        Some("--engine") => return engine::run_engine(),
+        // the rest of the line is the program run for sides played by an engine
+        Some("--engine-command") => config.engine = args.collect(),
+        _ => {}
+    }
     let mut app = App::new();
+    app.insert_resource(config);
     app.add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()));
     app.add_plugins((board::BoardPlugin, camera::CameraPlugin));
     app.add_plugins(pieces::PiecesPlugin);
+    app.add_plugins(rules::RulesPlugin);
+    app.add_plugins((
+        game::GamePlugin,
+        menu::MenuPlugin,
+        hud::HudPlugin,
+        clock::ClockPlugin,
+        network::NetworkPlugin,
+        engine::EnginePlugin,
+    ));
     app.add_plugins(bevy::picking::mesh_picking::MeshPickingPlugin);
-    app.run();
+    app.run()
 }
@@ -0,0 +1,281 @@
+use bevy::prelude::*;
+
+use crate::board::{MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::clock::TimeControl;
+use crate::game::{AppState, GameConfig, PlayerKind};
+use crate::pieces::{StartingSetup, Team};
+use crate::rules::DrawRules;
+
+/// The screen shown before a game, every option for the next game is picked here
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Menu), spawn_menu)
+            .add_systems(
+                Update,
+                (press_buttons, update_option_text)
+                    .chain()
+                    .run_if(in_state(AppState::Menu)),
+            );
+    }
+}
+
+/// What pressing a menu button does
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuButton {
+    Option(MenuOption),
+    Start,
+}
+
+/// A line of the menu, the button on it cycles the option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuOption {
+    Dimensions,
+    BoardSize,
+    Setup,
+    FiftyMove,
+    Repetition,
+    InsufficientMaterial,
+    Player(Team),
+    AnyOrder,
+    TimeControl,
+    LiftPosition,
+    Network,
+}
+
+impl MenuOption {
+    const ALL: [MenuOption; 12] = [
+        MenuOption::Dimensions,
+        MenuOption::BoardSize,
+        MenuOption::Setup,
+        MenuOption::FiftyMove,
+        MenuOption::Repetition,
+        MenuOption::InsufficientMaterial,
+        MenuOption::Player(Team::White),
+        MenuOption::Player(Team::Black),
+        MenuOption::AnyOrder,
+        MenuOption::TimeControl,
+        MenuOption::LiftPosition,
+        MenuOption::Network,
+    ];
+
+    fn label(&self, config: &GameConfig) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match self {
+            MenuOption::Dimensions => format!("Dimensions: {}", config.dimensions),
+            MenuOption::BoardSize => format!("Board size: {}", config.board_size),
+            MenuOption::Setup => match config.setup {
+                StartingSetup::EverySlice
+                    if config.setup.filled_axes(config.dimensions) < config.dimensions =>
+                {
+                    format!(
+                        "Pieces: every 2D board on the first {} axes, any more is too many",
+                        StartingSetup::MAX_FILLED_AXES
+                    )
+                }
+                StartingSetup::EverySlice => String::from("Pieces: every 2D board"),
+                StartingSetup::FirstAxes { .. } => format!(
+                    "Pieces: boards on the first {} axes",
+                    config.setup.filled_axes(config.dimensions)
+                ),
+            },
+            MenuOption::FiftyMove => format!(
+                "Fifty move rule: {}",
+                on_off(config.draw_rules.halfmove_limit.is_some())
+            ),
+            MenuOption::Repetition => format!(
+                "Threefold repetition: {}",
+                on_off(config.draw_rules.repetition_limit.is_some())
+            ),
+            MenuOption::InsufficientMaterial => format!(
+                "Insufficient material: {}",
+                on_off(config.draw_rules.insufficient_material.is_some())
+            ),
+            MenuOption::Player(team) => format!("{team:?}: {:?}", config.player(*team)),
+            MenuOption::AnyOrder if config.any_order => {
+                String::from("Turns: either side any time, people only")
+            }
+            MenuOption::AnyOrder => String::from("Turns: take turns"),
+            MenuOption::TimeControl => format!("Clock: {}", config.time_control.name()),
+            MenuOption::LiftPosition if config.lift_position => {
+                String::from("Changing dimensions: keeps the position")
+            }
+            MenuOption::LiftPosition => String::from("Changing dimensions: starts a new game"),
+            MenuOption::Network => format!("Network: {}", config.network.name()),
+        }
+    }
+
+    /// Step the option to its next value, wrapping around
+    fn cycle(&self, config: &mut GameConfig) {
+        let defaults = DrawRules::default();
+        match self {
+            MenuOption::Dimensions => {
+                config.dimensions = if config.dimensions >= GameConfig::MAX_DIMENSIONS {
+                    GameConfig::MIN_DIMENSIONS
+                } else {
+                    config.dimensions + 1
+                };
+            }
+            MenuOption::BoardSize => {
+                config.board_size = if config.board_size >= MAX_BOARD_SIZE {
+                    MIN_BOARD_SIZE
+                } else {
+                    config.board_size + 1
+                };
+            }
+            MenuOption::Setup => {
+                config.setup = match config.setup {
+                    StartingSetup::FirstAxes { axes }
+                        if axes < config.dimensions.min(StartingSetup::MAX_FILLED_AXES) =>
+                    {
+                        StartingSetup::FirstAxes { axes: axes + 1 }
+                    }
+                    StartingSetup::FirstAxes { .. } => StartingSetup::EverySlice,
+                    StartingSetup::EverySlice => StartingSetup::FirstAxes { axes: 2 },
+                };
+            }
+            MenuOption::FiftyMove => {
+                let rules = &mut config.draw_rules;
+                rules.halfmove_limit = rules.halfmove_limit.xor(defaults.halfmove_limit);
+            }
+            MenuOption::Repetition => {
+                let rules = &mut config.draw_rules;
+                rules.repetition_limit = rules.repetition_limit.xor(defaults.repetition_limit);
+            }
+            MenuOption::InsufficientMaterial => {
+                let rules = &mut config.draw_rules;
+                rules.insufficient_material = match rules.insufficient_material {
+                    Some(_) => None,
+                    None => defaults.insufficient_material,
+                };
+            }
+            MenuOption::Player(Team::White) => config.white = config.white.next(),
+            MenuOption::Player(Team::Black) => config.black = config.black.next(),
+            MenuOption::AnyOrder => config.any_order = !config.any_order,
+            MenuOption::TimeControl => {
+                let presets = TimeControl::PRESETS;
+                let index = presets
+                    .iter()
+                    .position(|control| *control == config.time_control)
+                    .map_or(0, |i| (i + 1) % presets.len());
+                config.time_control = presets[index];
+            }
+            MenuOption::LiftPosition => config.lift_position = !config.lift_position,
+            MenuOption::Network => config.network = config.network.next(),
+        }
+        // a setup past the last axis would fill the same boards as every slice
+        if let StartingSetup::FirstAxes { axes } = &mut config.setup {
+            *axes = (*axes).min(config.dimensions);
+        }
+    }
+}
+
+impl PlayerKind {
+    fn next(&self) -> PlayerKind {
+        match self {
+            PlayerKind::Human => PlayerKind::Computer,
+            PlayerKind::Computer => PlayerKind::Engine,
+            // remote players are picked by hosting or joining a game
+            PlayerKind::Engine | PlayerKind::Remote => PlayerKind::Human,
+        }
+    }
+}
+
+const BUTTON_COLOR: Color = Color::srgb(0.2, 0.2, 0.25);
+const HOVERED_COLOR: Color = Color::srgb(0.3, 0.3, 0.4);
+
+fn spawn_menu(mut commands: Commands, config: Res<GameConfig>) {
+    let button = |action: MenuButton, label: String| {
+        (
+            Button,
+            action,
+            Node {
+                width: Val::Px(360.),
+                padding: UiRect::all(Val::Px(8.)),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            BackgroundColor(BUTTON_COLOR),
+            children![(
+                Text::new(label),
+                TextFont {
+                    font_size: 20.,
+                    ..Default::default()
+                },
+                Pickable::IGNORE,
+            )],
+        )
+    };
+    commands
+        .spawn((
+            Name::new("Main Menu"),
+            StateScoped(AppState::Menu),
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.),
+                ..Default::default()
+            },
+        ))
+        .with_children(|menu| {
+            menu.spawn((
+                Text::new("N-D Chess"),
+                TextFont {
+                    font_size: 48.,
+                    ..Default::default()
+                },
+            ));
+            for option in MenuOption::ALL {
+                menu.spawn(button(MenuButton::Option(option), option.label(&config)));
+            }
+            menu.spawn(button(MenuButton::Start, String::from("Start game")));
+        });
+}
+
+fn press_buttons(
+    mut buttons: Query<
+        (&Interaction, &MenuButton, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut config: ResMut<GameConfig>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    for (interaction, action, mut background) in &mut buttons {
+        background.0 = match interaction {
+            Interaction::None => BUTTON_COLOR,
+            _ => HOVERED_COLOR,
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match action {
+            MenuButton::Option(option) => option.cycle(&mut config),
+            MenuButton::Start => state.set(AppState::Playing),
+        }
+    }
+}
+
+/// The button text shows the current value of its option
+fn update_option_text(
+    config: Res<GameConfig>,
+    buttons: Query<(&MenuButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+    for (action, children) in &buttons {
+        let MenuButton::Option(option) = action else {
+            continue;
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.0 = option.label(&config);
+            }
+        }
+    }
+}
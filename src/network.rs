@@ -0,0 +1,722 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::board::{BoardState, MAX_BOARD_SIZE, MIN_BOARD_SIZE, Position};
+use crate::clock::{ChessClock, TimeControl};
+use crate::game::{AppState, GameConfig, InGame, NewGameSet, PlayerKind};
+use crate::pieces::{
+    ChessPiece, HasMoved, MoveMade, PlannedMove, StartingSetup, Team, checked_move, play_move,
+};
+use crate::rules::{DrawRules, GameOutcome};
+
+pub use server::run_server;
+
+mod server;
+
+/// Two player games over TCP, the host plays white and the player who joins plays black.
+/// Both sides check every move with their own rules, a move the other side sent that isn't legal here is dropped.
+/// Joining a headless server works the same way, except the server picks the side and anyone can join to watch.
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Network>()
+            .add_systems(
+                OnEnter(InGame),
+                (
+                    open_connection.in_set(NewGameSet::Configure),
+                    replay_moves.in_set(NewGameSet::Replay),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    accept_peer,
+                    receive_messages,
+                    sync_clock,
+                    play_remote_move,
+                    send_moves,
+                )
+                    .chain()
+                    .run_if(in_state(InGame)),
+            );
+    }
+}
+
+/// Whether this game is played over the network and which end of the connection this is
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum NetworkRole {
+    #[default]
+    Offline,
+    Host {
+        port: u16,
+    },
+    Join {
+        address: String,
+    },
+    /// join a server to watch, every move is played by the server
+    Spectate {
+        address: String,
+    },
+}
+
+impl NetworkRole {
+    pub const DEFAULT_PORT: u16 = 7878;
+
+    pub fn name(&self) -> String {
+        match self {
+            NetworkRole::Offline => String::from("Offline"),
+            NetworkRole::Host { port } => format!("Host on port {port}"),
+            NetworkRole::Join { address } => format!("Join {address}"),
+            NetworkRole::Spectate { address } => format!("Spectate {address}"),
+        }
+    }
+
+    /// Whether this end starts new games and changes the board, everyone else follows the host's
+    pub fn picks_game(&self) -> bool {
+        matches!(self, NetworkRole::Offline | NetworkRole::Host { .. })
+    }
+
+    /// Offline, hosting on the default port then joining or watching it on this machine
+    pub fn next(&self) -> NetworkRole {
+        let address = format!("127.0.0.1:{}", Self::DEFAULT_PORT);
+        match self {
+            NetworkRole::Offline => NetworkRole::Host {
+                port: Self::DEFAULT_PORT,
+            },
+            NetworkRole::Host { .. } => NetworkRole::Join { address },
+            NetworkRole::Join { .. } => NetworkRole::Spectate { address },
+            NetworkRole::Spectate { .. } => NetworkRole::Offline,
+        }
+    }
+}
+
+/// Sent as one line of RON each
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NetMessage {
+    /// the host starts a new game with these options whenever someone joins
+    NewGame {
+        dimensions: usize,
+        board_size: usize,
+        setup: StartingSetup,
+        draw_rules: DrawRules,
+        time_control: TimeControl,
+        /// the moves already played, for someone joining partway through or whose board is out of step
+        moves: Vec<(Vec<i8>, Vec<i8>)>,
+    },
+    Move {
+        from: Vec<i8>,
+        to: Vec<i8>,
+    },
+    /// sent to a server straight after connecting
+    Join {
+        spectate: bool,
+    },
+    /// the side a server gives the player who joined, `None` to watch when both sides are taken
+    Seat {
+        team: Option<Team>,
+    },
+    /// the server's clocks after a move
+    Clock(ClockSync),
+    /// the server has ended the game
+    Outcome(GameOutcome),
+}
+
+/// Both clocks as they were after `moves` half moves
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ClockSync {
+    pub moves: usize,
+    pub white: f32,
+    pub black: f32,
+}
+
+impl From<&ChessClock> for ClockSync {
+    fn from(clock: &ChessClock) -> Self {
+        Self {
+            moves: clock.moves,
+            white: clock.white,
+            black: clock.black,
+        }
+    }
+}
+
+/// The open connection, if any
+#[derive(Resource, Default)]
+pub struct Network {
+    listener: Option<TcpListener>,
+    peer: Option<Peer>,
+    /// moves received from the other player that haven't been played yet
+    pending: VecDeque<(Position, Position)>,
+    /// the side a server gave this end, `Some(None)` when watching
+    seat: Option<Option<Team>>,
+    /// waits for the moves before it to be played here
+    clock: Option<ClockSync>,
+    /// the moves the next game starts with, played for both sides as soon as it is set up
+    replay: Vec<(Position, Position)>,
+    /// replayed moves `send_moves` hasn't seen yet, the other end has already played them
+    replayed: usize,
+}
+
+impl Network {
+    /// Who makes `team`'s moves this game, a side played at the other end of the connection is `Remote`
+    /// whatever the config says, so the config is still the one picked in the menu once the game is over
+    pub fn player(&self, config: &GameConfig, team: Team) -> PlayerKind {
+        if config.network != NetworkRole::Offline && self.local_team(&config.network) != Some(team)
+        {
+            PlayerKind::Remote
+        } else {
+            config.player(team)
+        }
+    }
+
+    pub fn is_human(&self, config: &GameConfig, team: Team) -> bool {
+        self.player(config, team) == PlayerKind::Human
+    }
+
+    /// The side played on this end, `None` when every move arrives over the connection
+    fn local_team(&self, role: &NetworkRole) -> Option<Team> {
+        match role {
+            NetworkRole::Offline => None,
+            NetworkRole::Host { .. } => Some(Team::White),
+            NetworkRole::Join { .. } => self.seat.unwrap_or(Some(Team::Black)),
+            NetworkRole::Spectate { .. } => None,
+        }
+    }
+}
+
+/// The longest message a peer can send, anything longer drops the connection rather then being buffered forever
+const MAX_MESSAGE_LENGTH: usize = 1 << 20;
+
+struct Peer {
+    stream: TcpStream,
+    received: Vec<u8>,
+    /// written messages the socket didn't take yet
+    sending: Vec<u8>,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            received: Vec::new(),
+            sending: Vec::new(),
+        })
+    }
+
+    /// Queue `message` after any that are still waiting and send as much as the socket takes
+    fn send(&mut self, message: &NetMessage) -> Result<(), Box<dyn std::error::Error>> {
+        let mut line = ron::to_string(message)?;
+        line.push('\n');
+        self.sending.extend_from_slice(line.as_bytes());
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Send what is queued until the socket would block, the rest goes on a later frame
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut written = 0;
+        let result = loop {
+            if written == self.sending.len() {
+                break Ok(());
+            }
+            match self.stream.write(&self.sending[written..]) {
+                Ok(0) => break Err(ErrorKind::WriteZero.into()),
+                Ok(sent) => written += sent,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => break Err(error),
+            }
+        };
+        self.sending.drain(..written);
+        result
+    }
+
+    /// Every whole message that has arrived, `Err` once the connection is closed or a message is too long.
+    /// Also sends whatever is still queued.
+    fn receive(&mut self) -> std::io::Result<Vec<NetMessage>> {
+        self.flush()?;
+        let mut buffer = [0; 1024];
+        let mut messages = Vec::new();
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(read) => {
+                    self.received.extend_from_slice(&buffer[..read]);
+                    if buffer[..read].contains(&b'\n') {
+                        self.parse_lines(&mut messages);
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+            // what is left is the start of a message
+            if self.received.len() > MAX_MESSAGE_LENGTH {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("a message was longer then {MAX_MESSAGE_LENGTH} bytes"),
+                ));
+            }
+        }
+        Ok(messages)
+    }
+
+    fn parse_lines(&mut self, messages: &mut Vec<NetMessage>) {
+        while let Some(end) = self.received.iter().position(|&byte| byte == b'\n') {
+            let line = self.received.drain(..=end).collect::<Vec<_>>();
+            match ron::de::from_bytes::<NetMessage>(&line) {
+                Ok(message) => messages.push(message),
+                Err(error) => warn!("Ignoring a message that didn't parse: {error}"),
+            }
+        }
+    }
+}
+
+/// The cells of a move sent over the connection, `None` unless both are on the board `config` sets up
+fn move_cells(from: Vec<i8>, to: Vec<i8>, config: &GameConfig) -> Option<(Position, Position)> {
+    let cell = |cell: Vec<i8>| {
+        let on_board = cell.len() == config.dimensions
+            && cell
+                .iter()
+                .all(|&index| (0..config.board_size as i16).contains(&i16::from(index)));
+        on_board.then_some(Position(cell))
+    };
+    Some((cell(from)?, cell(to)?))
+}
+
+impl GameConfig {
+    /// A game with these options that has already had `moves` played
+    fn new_game_message(&self, moves: &[MoveMade]) -> NetMessage {
+        NetMessage::NewGame {
+            dimensions: self.dimensions,
+            board_size: self.board_size,
+            setup: self.setup,
+            draw_rules: self.draw_rules.clone(),
+            time_control: self.time_control,
+            moves: moves
+                .iter()
+                .map(|made| (made.from.0.clone(), made.to.0.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Host or join when a game starts, the other player's side is played by whatever arrives over the connection
+fn open_connection(mut network: ResMut<Network>, config: Res<GameConfig>) {
+    match config.network.clone() {
+        NetworkRole::Offline => {
+            network.listener = None;
+            network.peer = None;
+            network.pending.clear();
+        }
+        NetworkRole::Host { port } => {
+            network.pending.clear();
+            // a new game the host started, the other player starts it too
+            let message = config.new_game_message(&[]);
+            if let Some(peer) = &mut network.peer
+                && let Err(error) = peer.send(&message)
+            {
+                warn!("Couldn't send the new game to the other player: {error}");
+                network.peer = None;
+            }
+            if network.listener.is_none() {
+                match TcpListener::bind(("0.0.0.0", port))
+                    .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+                {
+                    Ok(listener) => {
+                        info!("Waiting for a player to join on port {port}");
+                        network.listener = Some(listener);
+                    }
+                    Err(error) => error!("Couldn't host on port {port}: {error}"),
+                }
+            }
+        }
+        // moves that arrived with the new game are for this game, so they are kept
+        NetworkRole::Join { address } | NetworkRole::Spectate { address } => {
+            network.listener = None;
+            if network.peer.is_none() {
+                let spectate = matches!(config.network, NetworkRole::Spectate { .. });
+                let peer = address
+                    .to_socket_addrs()
+                    .and_then(|mut addresses| {
+                        addresses
+                            .next()
+                            .ok_or_else(|| ErrorKind::AddrNotAvailable.into())
+                    })
+                    .and_then(|address| {
+                        TcpStream::connect_timeout(&address, Duration::from_secs(2))
+                    })
+                    .and_then(Peer::new);
+                match peer {
+                    Ok(mut peer) => {
+                        info!("Joined {address}, waiting for the host to start the game");
+                        if let Err(error) = peer.send(&NetMessage::Join { spectate }) {
+                            warn!("Couldn't send to {address}: {error}");
+                        }
+                        network.seat = None;
+                        network.peer = Some(peer);
+                    }
+                    Err(error) => error!("Couldn't join {address}: {error}"),
+                }
+            }
+        }
+    }
+}
+
+/// The host starts a fresh game for both players when someone joins, the game is sent as it starts
+fn accept_peer(mut network: ResMut<Network>, mut state: ResMut<NextState<AppState>>) {
+    let Some(listener) = &network.listener else {
+        return;
+    };
+    let stream = match listener.accept() {
+        Ok((stream, address)) => {
+            info!("{address} joined");
+            stream
+        }
+        Err(error) if error.kind() == ErrorKind::WouldBlock => return,
+        Err(error) => {
+            warn!("Couldn't accept a player: {error}");
+            return;
+        }
+    };
+    let peer = match Peer::new(stream) {
+        Ok(peer) => peer,
+        Err(error) => {
+            warn!("Couldn't set up the connection: {error}");
+            return;
+        }
+    };
+    network.peer = Some(peer);
+    state.set(AppState::NewGame);
+}
+
+fn receive_messages(
+    mut network: ResMut<Network>,
+    mut config: ResMut<GameConfig>,
+    mut state: ResMut<NextState<AppState>>,
+    outcome: Option<Res<GameOutcome>>,
+    mut commands: Commands,
+) {
+    let Some(peer) = &mut network.peer else {
+        return;
+    };
+    let messages = match peer.receive() {
+        Ok(messages) => messages,
+        Err(error) => {
+            warn!("The other player disconnected: {error}");
+            network.peer = None;
+            return;
+        }
+    };
+    for message in messages {
+        match message {
+            NetMessage::NewGame {
+                dimensions,
+                board_size,
+                setup,
+                draw_rules,
+                time_control,
+                moves,
+            } => {
+                // only the host picks the game
+                if config.network.picks_game() {
+                    continue;
+                }
+                let range = GameConfig::MIN_DIMENSIONS..=GameConfig::MAX_DIMENSIONS;
+                if !range.contains(&dimensions)
+                    || !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&board_size)
+                {
+                    warn!(
+                        "The host started a game on {dimensions} dimensions of size {board_size}, which can't be played here"
+                    );
+                    continue;
+                }
+                config.dimensions = dimensions;
+                config.board_size = board_size;
+                config.setup = setup;
+                config.draw_rules = draw_rules;
+                config.time_control = time_control;
+                // the moves after one that isn't on the board can't be played either
+                network.replay = moves
+                    .into_iter()
+                    .map_while(|(from, to)| move_cells(from, to, &config))
+                    .collect();
+                network.pending.clear();
+                network.clock = None;
+                state.set(AppState::NewGame);
+            }
+            // checked against the game the host has set, which may have arrived with these moves
+            NetMessage::Move { from, to } => match move_cells(from, to, &config) {
+                Some(cells) => network.pending.push_back(cells),
+                None => warn!("The other player sent a move off the board"),
+            },
+            NetMessage::Seat { team } => {
+                match team {
+                    Some(team) => info!("Playing as {team:?}"),
+                    None => info!("Both sides are taken, watching instead"),
+                }
+                network.seat = Some(team);
+            }
+            NetMessage::Clock(sync) => network.clock = Some(sync),
+            // the game ends here too, the server just knew first
+            NetMessage::Outcome(ended) => {
+                if outcome.is_none() {
+                    commands.insert_resource(ended);
+                }
+            }
+            // only a server seats players
+            NetMessage::Join { .. } => {}
+        }
+    }
+}
+
+/// A server's clocks replace the ones here once the same moves have been played
+fn sync_clock(mut network: ResMut<Network>, mut clock: ResMut<ChessClock>) {
+    let Some(sync) = network.clock.take_if(|sync| sync.moves <= clock.moves) else {
+        return;
+    };
+    if sync.moves == clock.moves {
+        clock.white = sync.white;
+        clock.black = sync.black;
+    }
+}
+
+/// Catch up with a game that started before this end joined it, or that this end fell out of step with
+fn replay_moves(world: &mut World) {
+    let moves = std::mem::take(&mut world.resource_mut::<Network>().replay);
+    let replayed = replay(world, moves);
+    world.resource_mut::<Network>().replayed += replayed;
+}
+
+/// Play `moves` for whichever side's turn it is, checked and played the same way the server plays them.
+/// Stops at the first illegal move, returns how many were played.
+fn replay(world: &mut World, moves: Vec<(Position, Position)>) -> usize {
+    let mut replayed = 0;
+    for step in moves {
+        let played = world
+            .run_system_once_with(replay_move, step)
+            .map_err(|error| error.to_string())
+            .and_then(|played| played);
+        if let Err(error) = played {
+            warn!("Couldn't catch up with the game: {error}");
+            break;
+        }
+        replayed += 1;
+    }
+    replayed
+}
+
+fn replay_move(
+    In((from, to)): In<(Position, Position)>,
+    mut board: ResMut<BoardState>,
+    mut turn: ResMut<Team>,
+    mut positions: Query<&mut Position, With<ChessPiece>>,
+    info: Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+    pieces: Query<(&ChessPiece, &Team)>,
+    mut moves: EventWriter<MoveMade>,
+    mut commands: Commands,
+) -> Result<(), String> {
+    let (entity, piece, kind) = checked_move(&from, &to, *turn, &board, &info)?;
+    let mut position = positions
+        .get_mut(entity)
+        .map_err(|error| error.to_string())?;
+    let made = play_move(
+        (entity, piece, *turn),
+        &mut position,
+        &to,
+        &kind,
+        &mut board,
+        &pieces,
+        &mut commands,
+    );
+    // the capture animation only picks up the last piece taken each frame
+    if let Some(captured) = board.take_captured() {
+        commands.entity(captured).despawn();
+    }
+    *turn = turn.opposite();
+    moves.write(made);
+    Ok(())
+}
+
+/// Play the other player's moves through selection like a local player's, so they are checked by `make_move`
+fn play_remote_move(
+    mut network: ResMut<Network>,
+    config: Res<GameConfig>,
+    turn: Res<Team>,
+    board: Res<BoardState>,
+    pieces: Query<&Team, With<ChessPiece>>,
+    mut planned: PlannedMove,
+) {
+    if network.player(&config, *turn) != PlayerKind::Remote {
+        planned.cancel();
+        return;
+    }
+    if planned.busy() {
+        return;
+    }
+    let Some((from, to)) = network.pending.pop_front() else {
+        return;
+    };
+    match board.get(&from) {
+        Some(piece) if pieces.get(piece).is_ok_and(|team| team == &*turn) => {
+            planned.start(piece, to);
+        }
+        _ => warn!("The other player tried to move from {from}, which has none of their pieces"),
+    }
+}
+
+fn send_moves(
+    mut moves: EventReader<MoveMade>,
+    mut network: ResMut<Network>,
+    config: Res<GameConfig>,
+) {
+    for made in moves.read() {
+        if network.replayed > 0 {
+            network.replayed -= 1;
+            continue;
+        }
+        // moves that came over the connection don't go back
+        if network.player(&config, made.team) == PlayerKind::Remote {
+            continue;
+        }
+        let Some(peer) = &mut network.peer else {
+            continue;
+        };
+        let message = NetMessage::Move {
+            from: made.from.0.clone(),
+            to: made.to.0.clone(),
+        };
+        if let Err(error) = peer.send(&message) {
+            warn!("Couldn't send {made} to the other player: {error}");
+            network.peer = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::starting_pieces;
+
+    fn new_game() -> World {
+        let mut world = World::new();
+        world.init_resource::<BoardState>();
+        world.init_resource::<Events<MoveMade>>();
+        world.insert_resource(Team::White);
+        for (position, piece, team) in starting_pieces(2, 8, StartingSetup::default()) {
+            world.spawn((position, piece, team));
+        }
+        world.flush();
+        world
+    }
+
+    fn play(world: &mut World, moves: &[(&str, &str)]) {
+        let moves = moves
+            .iter()
+            .map(|(from, to)| (from.parse().unwrap(), to.parse().unwrap()))
+            .collect::<Vec<_>>();
+        let count = moves.len();
+        assert_eq!(replay(world, moves), count);
+    }
+
+    fn pieces(world: &mut World) -> (Team, Vec<(Position, ChessPiece, Team, bool)>) {
+        let mut pieces = world
+            .query::<(&Position, &ChessPiece, &Team, Has<HasMoved>)>()
+            .iter(world)
+            .map(|(position, piece, team, moved)| (position.clone(), *piece, *team, moved))
+            .collect::<Vec<_>>();
+        pieces.sort_by_key(|(position, ..)| position.0.clone());
+        (*world.resource::<Team>(), pieces)
+    }
+
+    #[test]
+    fn a_dropped_move_is_undone_by_catching_up() {
+        let mut server = new_game();
+        let mut client = new_game();
+        let moves = [("e2", "e4"), ("d7", "d5"), ("e4", "d5"), ("g8", "f6")];
+        play(&mut server, &moves);
+        play(&mut client, &moves);
+        // the client played this but the server dropped it
+        play(&mut client, &[("f1", "b5")]);
+        assert_ne!(pieces(&mut client), pieces(&mut server));
+
+        let history = server
+            .resource_mut::<Events<MoveMade>>()
+            .drain()
+            .collect::<Vec<_>>();
+        let config = GameConfig {
+            dimensions: 2,
+            ..default()
+        };
+        let line = ron::to_string(&config.new_game_message(&history)).unwrap();
+        let Ok(NetMessage::NewGame { moves, .. }) = ron::from_str(&line) else {
+            panic!("{line} isn't a new game");
+        };
+        let moves = moves
+            .into_iter()
+            .map_while(|(from, to)| move_cells(from, to, &config))
+            .collect::<Vec<_>>();
+        let mut client = new_game();
+        assert_eq!(replay(&mut client, moves), history.len());
+        assert_eq!(pieces(&mut client), pieces(&mut server));
+    }
+
+    /// A peer and the other end of its connection
+    fn connected() -> (Peer, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let other = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        (Peer::new(stream).unwrap(), other)
+    }
+
+    #[test]
+    fn sends_the_socket_wont_take_go_out_later() {
+        let (mut peer, other) = connected();
+        let message = NetMessage::Move {
+            from: vec![0; 8],
+            to: vec![7; 8],
+        };
+        let mut sent = 0;
+        while peer.sending.is_empty() {
+            peer.send(&message).unwrap();
+            sent += 1;
+        }
+        let reader = std::thread::spawn(move || {
+            std::io::BufRead::lines(std::io::BufReader::new(other))
+                .take(sent)
+                .map(|line| ron::from_str::<NetMessage>(&line.unwrap()).unwrap())
+                .filter(|received| matches!(received, NetMessage::Move { .. }))
+                .count()
+        });
+        while !peer.sending.is_empty() {
+            peer.flush().unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(reader.join().unwrap(), sent);
+    }
+
+    #[test]
+    fn a_message_that_never_ends_drops_the_peer() {
+        let (mut peer, mut other) = connected();
+        let writer = std::thread::spawn(move || {
+            other.write_all(b"Move(from:[0,1],to:[0,3])\n").unwrap();
+            other.write_all(&vec![b' '; MAX_MESSAGE_LENGTH + 1])
+        });
+        let error = loop {
+            if let Err(error) = peer.receive() {
+                break error;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        drop(peer);
+        let _ = writer.join();
+    }
+}
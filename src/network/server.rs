@@ -0,0 +1,407 @@
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+
+use super::{ClockSync, NetMessage, Peer, move_cells};
+use crate::board::{BoardState, Dimensions, Position, RenderedAxes};
+use crate::clock::{ChessClock, ClockPlugin};
+use crate::game::{AppState, GameConfig, GameStatePlugin, InGame, NewGameSet};
+use crate::pieces::{
+    self, ChessPiece, HasMoved, MoveMade, StartingSetup, Team, checked_move, play_move,
+};
+use crate::rules::{GameOutcome, MoveHistory, RulesPlugin, check_draw_rules};
+
+/// How long the finished game is kept before the same players start the next one, in seconds
+const REMATCH_DELAY: f32 = 10.;
+
+/// Run a game with no window on `port` until the process is stopped.
+/// The first two players to join play white then black, everyone after them watches.
+/// Every move is checked here with the same rules the game uses and only legal moves are sent on,
+/// the clocks and the outcome are this server's and the players are kept in line with them.
+pub fn run_server(port: u16, config: GameConfig) -> AppExit {
+    let listener = match TcpListener::bind(("0.0.0.0", port))
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+    {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("Couldn't host on port {port}: {error}");
+            return AppExit::error();
+        }
+    };
+    App::new()
+        .add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                1. / 60.,
+            ))),
+            LogPlugin::default(),
+            StatesPlugin,
+        ))
+        .insert_resource(Dimensions(config.dimensions))
+        .insert_resource(config)
+        .init_resource::<RenderedAxes>()
+        .init_resource::<BoardState>()
+        .init_resource::<StartingSetup>()
+        .insert_resource(Team::White)
+        .add_event::<MoveMade>()
+        .add_plugins((GameStatePlugin, RulesPlugin, ClockPlugin))
+        .insert_resource(Server {
+            listener,
+            clients: Vec::new(),
+            next_id: 0,
+            moves: VecDeque::new(),
+        })
+        .add_systems(Startup, move || info!("Waiting for players on port {port}"))
+        .add_systems(
+            OnEnter(InGame),
+            (
+                spawn_pieces.in_set(NewGameSet::Spawn),
+                announce_game.in_set(NewGameSet::Record),
+            ),
+        )
+        .add_systems(OnExit(InGame), despawn_pieces)
+        .add_systems(OnEnter(AppState::GameOver), announce_outcome)
+        .add_systems(
+            Update,
+            (
+                accept_clients,
+                receive_from_clients,
+                start_when_seated.run_if(in_state(AppState::Menu)),
+                play_client_move.run_if(in_state(AppState::Playing)),
+                send_clock.run_if(in_state(InGame)),
+                rematch.run_if(in_state(AppState::GameOver)),
+            )
+                .chain()
+                // the history has every move played so far when it is sent to catch someone up
+                .after(check_draw_rules),
+        )
+        .run()
+}
+
+/// Every connection and the moves players have sent that haven't been checked yet
+#[derive(Resource)]
+struct Server {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    next_id: u64,
+    moves: VecDeque<ClientMove>,
+}
+
+struct Client {
+    id: u64,
+    peer: Peer,
+    seat: Seat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Seat {
+    /// connected but hasn't sent `Join` yet, nothing is sent to it
+    Waiting,
+    Player(Team),
+    Spectator,
+}
+
+struct ClientMove {
+    client: u64,
+    team: Team,
+    from: Position,
+    to: Position,
+}
+
+impl Server {
+    fn is_taken(&self, team: Team) -> bool {
+        self.clients
+            .iter()
+            .any(|client| client.seat == Seat::Player(team))
+    }
+
+    fn send_to(&mut self, id: u64, messages: &[NetMessage]) {
+        let Some(client) = self.clients.iter_mut().find(|client| client.id == id) else {
+            return;
+        };
+        for message in messages {
+            if let Err(error) = client.peer.send(message) {
+                warn!("Couldn't send to client {id}: {error}");
+            }
+        }
+    }
+
+    /// Send to everyone that has joined, apart from `skip`
+    fn broadcast(&mut self, message: &NetMessage, skip: Option<u64>) {
+        for client in &mut self.clients {
+            if client.seat == Seat::Waiting || Some(client.id) == skip {
+                continue;
+            }
+            // a closed connection is dropped next time it is read from
+            if let Err(error) = client.peer.send(message) {
+                warn!("Couldn't send to client {}: {error}", client.id);
+            }
+        }
+    }
+}
+
+/// Everything someone joining partway through needs to catch up with the game
+fn catch_up(
+    config: &GameConfig,
+    history: &MoveHistory,
+    clock: &ChessClock,
+    outcome: Option<&GameOutcome>,
+) -> Vec<NetMessage> {
+    let mut messages = vec![config.new_game_message(&history.moves)];
+    if clock.is_running() {
+        messages.push(NetMessage::Clock(ClockSync::from(clock)));
+    }
+    messages.extend(outcome.map(|outcome| NetMessage::Outcome(*outcome)));
+    messages
+}
+
+fn accept_clients(mut server: ResMut<Server>) {
+    loop {
+        let stream = match server.listener.accept() {
+            Ok((stream, address)) => {
+                info!("{address} connected");
+                stream
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => return,
+            Err(error) => {
+                warn!("Couldn't accept a client: {error}");
+                return;
+            }
+        };
+        match Peer::new(stream) {
+            Ok(peer) => {
+                let id = server.next_id;
+                server.next_id += 1;
+                server.clients.push(Client {
+                    id,
+                    peer,
+                    seat: Seat::Waiting,
+                });
+            }
+            Err(error) => warn!("Couldn't set up the connection: {error}"),
+        }
+    }
+}
+
+/// Seat clients as they join and queue the moves players send, a player leaving abandons the game
+fn receive_from_clients(
+    mut server: ResMut<Server>,
+    config: Res<GameConfig>,
+    history: Res<MoveHistory>,
+    clock: Res<ChessClock>,
+    outcome: Option<Res<GameOutcome>>,
+    state: Res<State<AppState>>,
+    mut next: ResMut<NextState<AppState>>,
+) {
+    let in_game = matches!(state.get(), AppState::Playing | AppState::GameOver);
+    let mut i = 0;
+    while i < server.clients.len() {
+        let messages = match server.clients[i].peer.receive() {
+            Ok(messages) => messages,
+            Err(error) => {
+                let client = server.clients.remove(i);
+                info!("Client {} left: {error}", client.id);
+                if let Seat::Player(team) = client.seat
+                    && in_game
+                {
+                    info!("{team:?} left, waiting for another player");
+                    next.set(AppState::Menu);
+                }
+                continue;
+            }
+        };
+        for message in messages {
+            let client = &server.clients[i];
+            let (id, seat) = (client.id, client.seat);
+            match message {
+                NetMessage::Join { spectate } if seat == Seat::Waiting => {
+                    let seat = if spectate {
+                        Seat::Spectator
+                    } else if !server.is_taken(Team::White) {
+                        Seat::Player(Team::White)
+                    } else if !server.is_taken(Team::Black) {
+                        Seat::Player(Team::Black)
+                    } else {
+                        Seat::Spectator
+                    };
+                    info!("Client {id} joined as {seat:?}");
+                    let team = match seat {
+                        Seat::Player(team) => Some(team),
+                        _ => None,
+                    };
+                    let mut messages = vec![NetMessage::Seat { team }];
+                    if in_game {
+                        messages.extend(catch_up(&config, &history, &clock, outcome.as_deref()));
+                    }
+                    server.clients[i].seat = seat;
+                    server.send_to(id, &messages);
+                }
+                NetMessage::Move { from, to } => {
+                    let Seat::Player(team) = seat else {
+                        warn!("Client {id} sent a move without playing a side");
+                        continue;
+                    };
+                    let Some((from, to)) = move_cells(from, to, &config) else {
+                        warn!("Client {id} sent a move off the board");
+                        let messages = catch_up(&config, &history, &clock, outcome.as_deref());
+                        server.send_to(id, &messages);
+                        continue;
+                    };
+                    server.moves.push_back(ClientMove {
+                        client: id,
+                        team,
+                        from,
+                        to,
+                    });
+                }
+                // everything else is only sent by the server
+                message => warn!("Ignoring {message:?} from client {id}"),
+            }
+        }
+        i += 1;
+    }
+}
+
+fn start_when_seated(server: Res<Server>, mut next: ResMut<NextState<AppState>>) {
+    if server.is_taken(Team::White) && server.is_taken(Team::Black) {
+        info!("Both players have joined, starting the game");
+        next.set(AppState::Playing);
+    }
+}
+
+/// The pieces only need to be on the board, there is nothing to show them with
+fn spawn_pieces(
+    mut commands: Commands,
+    dimensions: Res<Dimensions>,
+    board: Res<BoardState>,
+    setup: Res<StartingSetup>,
+) {
+    for (position, piece, team) in pieces::starting_pieces(**dimensions, board.size(), *setup) {
+        commands.spawn((position, piece, team));
+    }
+}
+
+fn despawn_pieces(
+    pieces: Query<Entity, With<ChessPiece>>,
+    mut board: ResMut<BoardState>,
+    mut server: ResMut<Server>,
+    mut commands: Commands,
+) {
+    for piece in &pieces {
+        commands.entity(piece).despawn();
+    }
+    board.clear();
+    server.moves.clear();
+}
+
+fn announce_game(mut server: ResMut<Server>, config: Res<GameConfig>) {
+    server.broadcast(&config.new_game_message(&[]), None);
+}
+
+fn announce_outcome(mut server: ResMut<Server>, outcome: Option<Res<GameOutcome>>) {
+    let Some(outcome) = outcome else {
+        return;
+    };
+    info!("Game over: {:?}", *outcome);
+    server.broadcast(&NetMessage::Outcome(*outcome), None);
+}
+
+/// Check the next move a player sent with the rules the game uses, a legal one is played
+/// and sent on to everyone else, the player that sent it has already played it.
+/// A dropped move has been played by its player too, so they are sent the whole game again to start over from.
+fn play_client_move(
+    mut server: ResMut<Server>,
+    config: Res<GameConfig>,
+    history: Res<MoveHistory>,
+    clock: Res<ChessClock>,
+    outcome: Option<Res<GameOutcome>>,
+    mut board: ResMut<BoardState>,
+    mut turn: ResMut<Team>,
+    mut positions: Query<&mut Position, With<ChessPiece>>,
+    info: Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+    pieces: Query<(&ChessPiece, &Team)>,
+    mut moves: EventWriter<MoveMade>,
+    mut commands: Commands,
+) {
+    // one a frame, the last move's promotion and `HasMoved` are only applied at the end of it
+    let Some(ClientMove {
+        client,
+        team,
+        from,
+        to,
+    }) = server.moves.pop_front()
+    else {
+        return;
+    };
+    let checked = if team == *turn {
+        checked_move(&from, &to, team, &board, &info)
+    } else {
+        Err(format!("{team:?} tried to move on {:?}'s turn", *turn))
+    };
+    let (entity, piece, kind) = match checked {
+        Ok(checked) => checked,
+        Err(error) => {
+            warn!("Dropping a move from client {client}: {error}");
+            server.send_to(
+                client,
+                &catch_up(&config, &history, &clock, outcome.as_deref()),
+            );
+            return;
+        }
+    };
+    let Ok(mut position) = positions.get_mut(entity) else {
+        return;
+    };
+    let made = play_move(
+        (entity, piece, team),
+        &mut position,
+        &to,
+        &kind,
+        &mut board,
+        &pieces,
+        &mut commands,
+    );
+    if let Some(captured) = board.take_captured() {
+        commands.entity(captured).despawn();
+    }
+    *turn = turn.opposite();
+    info!("{made}");
+    server.broadcast(
+        &NetMessage::Move {
+            from: from.0,
+            to: to.0,
+        },
+        Some(client),
+    );
+    moves.write(made);
+}
+
+/// Everyone gets the clocks after each move, the players' own clocks drift between moves
+fn send_clock(mut server: ResMut<Server>, clock: Res<ChessClock>, mut sent: Local<usize>) {
+    if !clock.is_running() || clock.moves == *sent {
+        return;
+    }
+    *sent = clock.moves;
+    server.broadcast(&NetMessage::Clock(ClockSync::from(&*clock)), None);
+}
+
+fn rematch(
+    server: Res<Server>,
+    time: Res<Time>,
+    mut waited: Local<f32>,
+    mut next: ResMut<NextState<AppState>>,
+) {
+    *waited += time.delta_secs();
+    if *waited < REMATCH_DELAY {
+        return;
+    }
+    *waited = 0.;
+    if server.is_taken(Team::White) && server.is_taken(Team::Black) {
+        next.set(AppState::NewGame);
+    }
+}
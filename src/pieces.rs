@@ -1,40 +1,75 @@
 use bevy::ecs::entity;
+use bevy::ecs::system::SystemParam;
 use bevy::ecs::world::DeferredWorld;
+use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
 use bevy::{ecs::component::HookContext, render::mesh::VertexAttributeValues};
 
 use bevy::prelude::*;
 
-use crate::board::{self, BoardState, NewPositionIter, Position, PositionIter};
-use crate::board::{DimensionIter, WithOffset};
+use crate::board::{self, BoardState, NewPositionIter, Position, PositionIter, RenderedAxes};
+use crate::board::{BoardRoot, DimensionIter, HoveredCell, MAX_REACH, WithOffset};
+use crate::game::{GameConfig, InGame, LiftPosition, NewGameSet};
+use crate::network::Network;
 use crate::pieces::move_iterators::{BishopMoveIterator, KnightMoveIterator, LMoveIter};
+use crate::rules::{DrawReason, GameOutcome, MoveHistory, position_key, position_key_with};
 
+pub use animation::{Captured, MoveAnimation, MoveTween};
+pub use attack_map::KingSafety;
+pub use computer::choose_move;
+pub use cursor::{CellCursor, CursorBindings};
+pub use special_moves::HasMoved;
+
+mod animation;
+mod attack_map;
+mod computer;
+mod cursor;
+mod indicators;
 mod move_iterators;
+mod piece_set;
+mod special_moves;
 
 pub struct PiecesPlugin;
 
 impl Plugin for PiecesPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<PieceAssets>()
-            .init_resource::<PossibleMoveAssets>();
-        app.add_systems(Startup, (spawn_pieces, spawn_select_indicator));
+            .init_resource::<PossibleMoveAssets>()
+            .init_resource::<StartingSetup>()
+            .add_event::<MoveMade>()
+            .add_event::<IllegalMove>()
+            .add_plugins((
+                animation::AnimationPlugin,
+                attack_map::AttackMapPlugin,
+                indicators::IndicatorsPlugin,
+                piece_set::PieceSetPlugin,
+                cursor::CursorPlugin,
+                computer::ComputerPlugin,
+            ));
+        app.add_systems(Startup, spawn_select_indicator)
+            .add_systems(OnEnter(InGame), spawn_pieces.in_set(NewGameSet::Spawn))
+            .add_systems(OnExit(InGame), despawn_pieces);
         app.add_systems(
             Update,
             (
-                update_piece_position,
+                animation::update_piece_position,
                 display_selected_piece,
                 clean_up_possible_moves,
-            ),
+            )
+                .run_if(in_state(InGame)),
         );
         app.insert_resource(Team::White);
         app.add_observer(only_select_one);
         app.add_observer(select_piece);
         app.add_observer(display_possible_moves)
-            .add_observer(make_move);
+            .add_observer(click_possible_move)
+            .add_observer(click_cell)
+            .add_observer(make_move)
+            .add_observer(lift_position);
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component, serde::Deserialize)]
 #[component(on_insert = Self::on_insert)]
 #[require(Team, Position)]
 pub enum ChessPiece {
@@ -46,7 +81,19 @@ pub enum ChessPiece {
     King,
 }
 
-#[derive(Component, PartialEq, Eq, Clone, Copy, Resource, Default)]
+#[derive(
+    Component,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Copy,
+    Resource,
+    Default,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub enum Team {
     #[default]
     White,
@@ -60,6 +107,14 @@ impl Team {
             Team::Black => Team::White,
         }
     }
+
+    /// The index on axis 0 where this team's pawns promote on a board of `size`
+    pub fn promotion_rank(&self, size: i8) -> i8 {
+        match self {
+            Team::White => size - 1,
+            Team::Black => 0,
+        }
+    }
 }
 
 impl ChessPiece {
@@ -71,6 +126,7 @@ impl ChessPiece {
         pieces: &Query<&Team>,
     ) -> Vec<Position> {
         let dimensions = position.len();
+        let size = board.size();
         let mut moves = Vec::new();
         match self {
             ChessPiece::Pawn => {
@@ -87,7 +143,7 @@ impl ChessPiece {
                         }
                     }
                     // try capturing diagonally
-                    for pos in PositionIter::<1>::start_at(dimensions, 1).with_offset(&next) {
+                    for pos in PositionIter::<1>::start_at(dimensions, 1).with_offset(&next, size) {
                         if pos == next {
                             continue;
                         }
@@ -102,7 +158,7 @@ impl ChessPiece {
                     let next = position.clone().dec(0);
                     if board.get(&next).is_none() {
                         moves.push(next.clone());
-                        if position[0] == 6 {
+                        if position[0] == size - 2 {
                             let next = next.clone().dec(0);
                             if board.get(&next).is_none() {
                                 moves.push(next);
@@ -110,7 +166,7 @@ impl ChessPiece {
                         }
                     }
                     // try capturing diagonally
-                    for pos in PositionIter::<1>::start_at(dimensions, 1).with_offset(&next) {
+                    for pos in PositionIter::<1>::start_at(dimensions, 1).with_offset(&next, size) {
                         if pos == next {
                             continue;
                         }
@@ -124,7 +180,7 @@ impl ChessPiece {
             }
             ChessPiece::King => {
                 let dec = position.dec_all();
-                for pos in NewPositionIter::<2>::new(position.len()).with_offset(&dec) {
+                for pos in NewPositionIter::<2>::new(position.len()).with_offset(&dec, size) {
                     if pos == *position {
                         continue;
                     }
@@ -139,8 +195,8 @@ impl ChessPiece {
             }
             ChessPiece::Rook => {
                 for axis in 0..dimensions {
-                    for next in
-                        DimensionIter::<7>::new(position.len(), axis, true).with_offset(position)
+                    for next in DimensionIter::<MAX_REACH>::new(position.len(), axis, true)
+                        .with_offset(position, size)
                     {
                         if next == *position {
                             continue;
@@ -155,8 +211,8 @@ impl ChessPiece {
                         }
                         moves.push(next.clone());
                     }
-                    for next in
-                        DimensionIter::<7>::new(position.len(), axis, false).with_offset(position)
+                    for next in DimensionIter::<MAX_REACH>::new(position.len(), axis, false)
+                        .with_offset(position, size)
                     {
                         if next == *position {
                             continue;
@@ -175,7 +231,7 @@ impl ChessPiece {
             }
             ChessPiece::Bishop => {
                 for diagonal in BishopMoveIterator::new(dimensions) {
-                    for next in diagonal.with_offset(position) {
+                    for next in diagonal.with_offset(position, size) {
                         if next == *position {
                             continue;
                         }
@@ -193,7 +249,7 @@ impl ChessPiece {
             }
             ChessPiece::Knight => {
                 for step in KnightMoveIterator::new(dimensions) {
-                    for next in step.with_offset(position) {
+                    for next in step.with_offset(position, size) {
                         if next == *position {
                             continue;
                         }
@@ -210,8 +266,8 @@ impl ChessPiece {
             ChessPiece::Queen => {
                 // rook like
                 for axis in 0..dimensions {
-                    for next in
-                        DimensionIter::<7>::new(position.len(), axis, true).with_offset(position)
+                    for next in DimensionIter::<MAX_REACH>::new(position.len(), axis, true)
+                        .with_offset(position, size)
                     {
                         if next == *position {
                             continue;
@@ -226,8 +282,8 @@ impl ChessPiece {
                         }
                         moves.push(next.clone());
                     }
-                    for next in
-                        DimensionIter::<7>::new(position.len(), axis, false).with_offset(position)
+                    for next in DimensionIter::<MAX_REACH>::new(position.len(), axis, false)
+                        .with_offset(position, size)
                     {
                         if next == *position {
                             continue;
@@ -245,7 +301,7 @@ impl ChessPiece {
                 }
                 // bishop like
                 for diagonal in BishopMoveIterator::new(dimensions) {
-                    for next in diagonal.with_offset(position) {
+                    for next in diagonal.with_offset(position, size) {
                         if next == *position {
                             continue;
                         }
@@ -268,6 +324,106 @@ impl ChessPiece {
         moves
     }
 
+    /// Every cell this piece attacks, unlike `all_possible_moves` this includes cells held by its own team
+    pub fn attacked_cells(
+        &self,
+        position: &Position,
+        team: Team,
+        board: &BoardState,
+    ) -> Vec<Position> {
+        let dimensions = position.len();
+        let size = board.size();
+        let mut attacks = Vec::new();
+        let slide = |attacks: &mut Vec<Position>, line: &mut dyn Iterator<Item = Position>| {
+            for next in line {
+                if next == *position {
+                    continue;
+                }
+                let blocked = board.get(&next).is_some();
+                attacks.push(next);
+                if blocked {
+                    break;
+                }
+            }
+        };
+        match self {
+            ChessPiece::Pawn => {
+                let next = if team == Team::White {
+                    position.clone().inc(0)
+                } else {
+                    position.clone().dec(0)
+                };
+                for pos in PositionIter::<1>::start_at(dimensions, 1).with_offset(&next, size) {
+                    if pos != next {
+                        attacks.push(pos);
+                    }
+                }
+            }
+            ChessPiece::King => {
+                let dec = position.dec_all();
+                for pos in NewPositionIter::<2>::new(dimensions).with_offset(&dec, size) {
+                    if pos != *position {
+                        attacks.push(pos);
+                    }
+                }
+            }
+            ChessPiece::Knight => {
+                for step in KnightMoveIterator::new(dimensions) {
+                    for next in step.with_offset(position, size) {
+                        if next != *position {
+                            attacks.push(next);
+                        }
+                    }
+                }
+            }
+            ChessPiece::Rook | ChessPiece::Bishop | ChessPiece::Queen => {
+                if *self != ChessPiece::Bishop {
+                    for axis in 0..dimensions {
+                        slide(
+                            &mut attacks,
+                            &mut DimensionIter::<MAX_REACH>::new(dimensions, axis, true)
+                                .with_offset(position, size),
+                        );
+                        slide(
+                            &mut attacks,
+                            &mut DimensionIter::<MAX_REACH>::new(dimensions, axis, false)
+                                .with_offset(position, size),
+                        );
+                    }
+                }
+                if *self != ChessPiece::Rook {
+                    for diagonal in BishopMoveIterator::new(dimensions) {
+                        slide(&mut attacks, &mut diagonal.with_offset(position, size));
+                    }
+                }
+            }
+        }
+        attacks
+    }
+
+    /// Rough material value used when weighing exchanges
+    pub fn value(&self) -> i32 {
+        match self {
+            ChessPiece::Pawn => 1,
+            ChessPiece::Knight | ChessPiece::Bishop => 3,
+            ChessPiece::Rook => 5,
+            ChessPiece::Queen => 9,
+            ChessPiece::King => 100,
+        }
+    }
+
+    /// The letter used for this piece in the move list, pawns have none
+    pub fn letter(&self) -> &'static str {
+        match self {
+            ChessPiece::Pawn => "",
+            ChessPiece::Rook => "R",
+            ChessPiece::Knight => "N",
+            ChessPiece::Bishop => "B",
+            ChessPiece::Queen => "Q",
+            ChessPiece::King => "K",
+        }
+    }
+
     pub fn on_insert(mut world: DeferredWorld, ctx: HookContext) {
         let piece = *world
             .get::<ChessPiece>(ctx.entity)
@@ -276,26 +432,29 @@ impl ChessPiece {
             .get::<Position>(ctx.entity)
             .expect("Just added ChessPiece, must have Position")
             .clone();
-        let assets = world.resource::<PieceAssets>();
-        let mesh = match piece {
-            ChessPiece::Pawn => assets.meshes[0].clone(),
-            ChessPiece::Rook => assets.meshes[1].clone(),
-            ChessPiece::King => assets.meshes[2].clone(),
-            ChessPiece::Bishop => assets.meshes[3].clone(),
-            ChessPiece::Knight => assets.meshes[4].clone(),
-            ChessPiece::Queen => assets.meshes[5].clone(),
-        };
+        // the headless server has no meshes, only the board
+        let mesh = world.get_resource::<PieceAssets>().map(|assets| {
+            let set = world
+                .resource::<piece_set::PieceSets>()
+                .current
+                .as_ref()
+                .and_then(|set| world.resource::<Assets<piece_set::PieceSet>>().get(set));
+            assets.mesh(piece, set, world.resource::<Assets<Mesh>>())
+        });
         let mut commands = world.commands();
-        commands
-            .entity(ctx.entity)
-            .insert((Name::new(format!("{piece:?}")), Mesh3d(mesh)));
+        let mut entity = commands.entity(ctx.entity);
+        entity.insert(Name::new(format!("{piece:?}")));
+        if let Some(mesh) = mesh {
+            entity.insert(Mesh3d(mesh));
+        }
         world.resource_mut::<BoardState>().set(position, ctx.entity);
     }
 }
 
+/// The primitive piece set, always loaded so it can stand in for anything a piece set is missing
 #[derive(Resource)]
 struct PieceAssets {
-    meshes: Vec<Handle<Mesh>>,
+    meshes: HashMap<ChessPiece, Handle<Mesh>>,
     white_material: Handle<StandardMaterial>,
     black_material: Handle<StandardMaterial>,
 }
@@ -316,7 +475,14 @@ impl FromWorld for PieceAssets {
             ..Default::default()
         });
         Self {
-            meshes: vec![cube, cylinder, sphere, cone, capsule, torus],
+            meshes: HashMap::from_iter([
+                (ChessPiece::Pawn, cube),
+                (ChessPiece::Rook, cylinder),
+                (ChessPiece::King, sphere),
+                (ChessPiece::Bishop, cone),
+                (ChessPiece::Knight, capsule),
+                (ChessPiece::Queen, torus),
+            ]),
             white_material,
             black_material,
         }
@@ -326,176 +492,123 @@ impl FromWorld for PieceAssets {
 fn spawn_pieces(
     mut commands: Commands,
     dimensions: Res<super::board::Dimensions>,
+    axes: Res<RenderedAxes>,
+    setup: Res<StartingSetup>,
+    board: Res<BoardState>,
     assets: Res<PieceAssets>,
 ) {
-    for position in PieceIter::new(**dimensions) {
-        if position[0] == 1 {
-            let mut piece = commands.spawn((
-                Name::new("White Pawn"),
-                position.clone(),
-                ChessPiece::Pawn,
-                Team::White,
-                MeshMaterial3d(assets.white_material.clone()),
-            ));
-            if position.is_visible(**dimensions) {
-                piece.insert(Visibility::Visible);
-            } else {
-                piece.insert(Visibility::Hidden);
-            }
-            continue;
-        } else if position[0] == 6 {
-            let mut piece = commands.spawn((
-                Name::new("Black Pawn"),
-                position.clone(),
-                ChessPiece::Pawn,
-                Team::Black,
-                MeshMaterial3d(assets.black_material.clone()),
-            ));
-            if position.is_visible(**dimensions) {
-                piece.insert(Visibility::Visible);
-            } else {
-                piece.insert(Visibility::Hidden);
-            }
-            continue;
-        }
-        if position[0] != 0 && position[0] != 7 {
-            continue;
-        }
-        let team = if position[0] == 0 {
-            Team::White
+    for (position, piece, team) in starting_pieces(**dimensions, board.size(), *setup) {
+        let material = match team {
+            Team::White => assets.white_material.clone(),
+            Team::Black => assets.black_material.clone(),
+        };
+        let visibility = if position.is_visible(&axes) {
+            Visibility::Visible
         } else {
-            Team::Black
+            Visibility::Hidden
         };
-        // king check
-        if position.if_is(1, 4) {
-            let mut piece = commands.spawn((position.clone(), ChessPiece::King, team));
-            if team == Team::White {
-                piece.insert((
-                    Name::new("White King"),
-                    MeshMaterial3d(assets.white_material.clone()),
-                ));
-            } else {
-                piece.insert((
-                    Name::new("Black King"),
-                    MeshMaterial3d(assets.black_material.clone()),
-                ));
-            }
-            if position.is_visible(**dimensions) {
-                piece.insert(Visibility::Visible);
-            } else {
-                piece.insert(Visibility::Hidden);
-            }
-            continue;
-        }
-        // rook check
-        if position.if_is(1, 0) || position.if_is(1, 7) {
-            let mut piece = commands.spawn((position.clone(), ChessPiece::Rook, team));
-            if team == Team::White {
-                piece.insert((
-                    Name::new("White Rook"),
-                    MeshMaterial3d(assets.white_material.clone()),
-                ));
-            } else {
-                piece.insert((
-                    Name::new("Black Rook"),
-                    MeshMaterial3d(assets.black_material.clone()),
-                ));
-            }
-            if position.is_visible(**dimensions) {
-                piece.insert(Visibility::Visible);
-            } else {
-                piece.insert(Visibility::Hidden);
-            }
-            continue;
-        }
-        // bishop check
-        if position.if_is(1, 2) || position.if_is(1, 5) {
-            let mut piece = commands.spawn((position.clone(), ChessPiece::Bishop, team));
-            if team == Team::White {
-                piece.insert((
-                    Name::new("White Bishop"),
-                    MeshMaterial3d(assets.white_material.clone()),
-                ));
-            } else {
-                piece.insert((
-                    Name::new("Black Bishop"),
-                    MeshMaterial3d(assets.black_material.clone()),
-                ));
-            }
-            if position.is_visible(**dimensions) {
-                piece.insert(Visibility::Visible);
-            } else {
-                piece.insert(Visibility::Hidden);
-            }
-            continue;
-        }
-        // knight check
-        if position.if_is(1, 1) || position.if_is(1, 6) {
-            let mut piece = commands.spawn((position.clone(), ChessPiece::Knight, team));
-            if team == Team::White {
-                piece.insert((
-                    Name::new("White Knight"),
-                    MeshMaterial3d(assets.white_material.clone()),
-                ));
-            } else {
-                piece.insert((
-                    Name::new("Black Knight"),
-                    MeshMaterial3d(assets.black_material.clone()),
-                ));
-            }
-            if position.is_visible(**dimensions) {
-                piece.insert(Visibility::Visible);
-            } else {
-                piece.insert(Visibility::Hidden);
-            }
-            continue;
-        }
-        // Queen check
-        if position.if_is(1, 3) {
-            let mut piece = commands.spawn((position.clone(), ChessPiece::Queen, team));
-            if team == Team::White {
-                piece.insert((
-                    Name::new("White Queen"),
-                    MeshMaterial3d(assets.white_material.clone()),
-                ));
-            } else {
-                piece.insert((
-                    Name::new("Black Queen"),
-                    MeshMaterial3d(assets.black_material.clone()),
-                ));
-            }
-            if position.is_visible(**dimensions) {
-                piece.insert(Visibility::Visible);
-            } else {
-                piece.insert(Visibility::Hidden);
-            }
-            continue;
+        commands.spawn((
+            Name::new(format!("{team:?} {piece:?}")),
+            position,
+            piece,
+            team,
+            MeshMaterial3d(material),
+            visibility,
+        ));
+    }
+}
+
+/// Every piece at the start of a game on a board of `size`, the back rows on the first and last rank
+/// and pawns in front of them, on every board `setup` fills
+pub fn starting_pieces(
+    dimensions: usize,
+    size: i8,
+    setup: StartingSetup,
+) -> impl Iterator<Item = (Position, ChessPiece, Team)> {
+    let last = size - 1;
+    PieceIter::new(dimensions, size, setup).filter_map(move |position| {
+        let team = match position[0] {
+            0 | 1 => Team::White,
+            rank if rank >= last - 1 => Team::Black,
+            _ => return None,
+        };
+        let piece = if position[0] == 1 || position[0] == last - 1 {
+            ChessPiece::Pawn
+        } else {
+            back_row_piece(position[1], last)
+        };
+        Some((position, piece, team))
+    })
+}
+
+/// The queen and king stand in the middle, on d and e of 8 files,
+/// and going in from either edge there is a rook, a knight and then bishops
+fn back_row_piece(file: i8, last: i8) -> ChessPiece {
+    let king = (last + 1) / 2;
+    match file.min(last - file) {
+        _ if file == king => ChessPiece::King,
+        _ if file == king - 1 => ChessPiece::Queen,
+        0 => ChessPiece::Rook,
+        1 => ChessPiece::Knight,
+        _ => ChessPiece::Bishop,
+    }
+}
+
+/// Which 2D boards get a set of pieces at the start
+#[derive(
+    Resource, Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+pub enum StartingSetup {
+    /// every 2D board in every slice, up to `MAX_FILLED_AXES`
+    #[default]
+    EverySlice,
+    /// only boards where every axis past the first `axes` is 0, keeps high dimension games to a playable number of pieces
+    FirstAxes { axes: usize },
+}
+
+impl StartingSetup {
+    /// Past this many axes there are millions of pieces, the boards on slice 0 of the rest are the only ones filled
+    pub const MAX_FILLED_AXES: usize = 5;
+
+    /// How many of the first axes get every one of their 2D boards filled
+    pub fn filled_axes(&self, dimensions: usize) -> usize {
+        let most = dimensions.min(Self::MAX_FILLED_AXES);
+        match self {
+            StartingSetup::EverySlice => most,
+            StartingSetup::FirstAxes { axes } => (*axes).clamp(2.min(dimensions), most),
         }
     }
 }
 
 struct PieceIter {
     current: super::board::Position,
+    dimensions: usize,
+    size: i8,
 }
 
 impl PieceIter {
-    fn new(dimensions: usize) -> Self {
-        let current = super::board::Position(vec![0; dimensions]);
-        Self { current }
+    fn new(dimensions: usize, size: i8, setup: StartingSetup) -> Self {
+        let current = super::board::Position(vec![0; setup.filled_axes(dimensions)]);
+        Self {
+            current,
+            dimensions,
+            size,
+        }
     }
 }
 
 impl Iterator for PieceIter {
     type Item = super::board::Position;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current[0] > 7 {
+        if self.current[0] >= self.size {
             return None;
         }
-        let out = self.current.clone();
+        let mut out = self.current.clone();
+        out.0.resize(self.dimensions, 0);
         *self.current.0.last_mut()? += 1;
         'out: loop {
             for i in (0..self.current.0.len()).rev() {
-                if self.current[i] > 7 && i != 0 {
+                if self.current[i] >= self.size && i != 0 {
                     self.current.0[i] = 0;
                     self.current.0[i - 1] += 1;
                     continue 'out;
@@ -507,16 +620,9 @@ impl Iterator for PieceIter {
     }
 }
 
-fn update_piece_position(
-    mut query: Query<(&super::board::Position, &mut Transform), Changed<super::board::Position>>,
-) {
-    for (position, mut transform) in query.iter_mut() {
-        transform.translation = position.to_translation();
-    }
-}
-
+/// The piece the player is about to move
 #[derive(Component)]
-struct Selected;
+pub struct Selected;
 
 fn only_select_one(
     trigger: Trigger<OnAdd, Selected>,
@@ -531,13 +637,40 @@ fn only_select_one(
     }
 }
 
+/// Whether a player here can move `team`'s pieces now, only the side to move can
+/// unless the game is played in any order
+fn can_move_team(team: Team, turn: Team, config: &GameConfig, network: &Network) -> bool {
+    config.plays_in_any_order() || (team == turn && network.is_human(config, turn))
+}
+
 fn select_piece(
     trigger: Trigger<Pointer<Click>>,
     mut commands: Commands,
-    can_select: Query<(), With<ChessPiece>>,
-    selected: Query<Entity, With<Selected>>,
+    can_select: Query<(&Team, &Position), With<ChessPiece>>,
+    selected: Query<(Entity, &Team), With<Selected>>,
+    turn: Res<Team>,
+    config: Res<GameConfig>,
+    network: Res<Network>,
+    outcome: Option<Res<GameOutcome>>,
 ) {
-    if can_select.get(trigger.target()).is_err() {
+    if outcome.is_some() {
+        return;
+    }
+    let Ok((team, position)) = can_select.get(trigger.target()) else {
+        return;
+    };
+    // clicking an enemy of the selected piece tries to take it
+    if let Some((_, mover)) = selected.iter().next()
+        && mover != team
+    {
+        if can_move_team(*mover, *turn, &config, &network) {
+            commands.trigger(RequestMove {
+                to: position.clone(),
+            });
+        }
+        return;
+    }
+    if !can_move_team(*team, *turn, &config, &network) {
         return;
     }
     if selected.contains(trigger.target()) {
@@ -547,6 +680,40 @@ fn select_piece(
     }
 }
 
+/// Clicking a cell selects the piece standing on it, or moves the selected piece there
+fn click_cell(
+    trigger: Trigger<Pointer<Click>>,
+    roots: Query<(), With<BoardRoot>>,
+    hovered: Res<HoveredCell>,
+    board: Res<BoardState>,
+    can_select: Query<&Team, With<ChessPiece>>,
+    selected: Query<&Team, With<Selected>>,
+    turn: Res<Team>,
+    config: Res<GameConfig>,
+    network: Res<Network>,
+    outcome: Option<Res<GameOutcome>>,
+    mut commands: Commands,
+) {
+    if outcome.is_some() || !roots.contains(trigger.target()) {
+        return;
+    }
+    let Some(cell) = &hovered.0 else {
+        return;
+    };
+    let mover = selected.iter().next().copied();
+    if let Some(piece) = board.get(cell)
+        && let Ok(team) = can_select.get(piece)
+        && mover.is_none_or(|mover| mover == *team)
+        && can_move_team(*team, *turn, &config, &network)
+    {
+        commands.entity(piece).insert(Selected);
+        return;
+    }
+    if mover.is_some_and(|mover| can_move_team(mover, *turn, &config, &network)) {
+        commands.trigger(RequestMove { to: cell.clone() });
+    }
+}
+
 #[derive(Component)]
 struct SelectIndicator;
 
@@ -579,20 +746,173 @@ fn display_selected_piece(
 #[derive(Component)]
 struct PossibleMove;
 
+/// Put the pieces on a board with a different number of axes, the game's history starts again from here
+fn lift_position(
+    trigger: Trigger<LiftPosition>,
+    mut pieces: Query<(Entity, &mut Position), With<ChessPiece>>,
+    keys: Query<(&ChessPiece, &Team)>,
+    selected: Query<Entity, With<Selected>>,
+    mut select_indicator: Query<(Entity, &mut Visibility), With<SelectIndicator>>,
+    mut board: ResMut<BoardState>,
+    mut history: ResMut<MoveHistory>,
+    turn: Res<Team>,
+    mut last_move: ResMut<indicators::LastMove>,
+    mut cursor: ResMut<CellCursor>,
+    mut commands: Commands,
+) {
+    let dimensions = trigger.dimensions;
+    // its possible moves are on the old board, they are cleaned up once nothing is selected
+    for entity in &selected {
+        commands.entity(entity).remove::<Selected>();
+    }
+    // the selected piece may be taken off, and the indicator with it
+    for (indicator, mut visibility) in &mut select_indicator {
+        *visibility = Visibility::Hidden;
+        commands.entity(indicator).remove::<ChildOf>();
+    }
+    board.clear();
+    for (entity, mut position) in &mut pieces {
+        if position.iter().skip(dimensions).any(|&index| index != 0) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        position.0.resize(dimensions, 0);
+        board.set(position.clone(), entity);
+    }
+    // positions on the old board can't repeat on this one
+    *history = MoveHistory::starting_from(position_key(&board, &keys, *turn));
+    // these are on cells of the old board
+    last_move.0 = None;
+    *cursor = CellCursor::default();
+}
+
+/// Clear the last game's pieces away, the board is cleared by being scoped to `InGame`
+fn despawn_pieces(
+    pieces: Query<Entity, Or<(With<ChessPiece>, With<Captured>, With<PossibleMove>)>>,
+    select_indicator: Single<(Entity, &mut Visibility), With<SelectIndicator>>,
+    mut board: ResMut<BoardState>,
+    mut commands: Commands,
+) {
+    // the indicator is kept for the next game, take it off the selected piece first
+    let (indicator, mut visibility) = select_indicator.into_inner();
+    *visibility = Visibility::Hidden;
+    commands.entity(indicator).remove::<ChildOf>();
+    for piece in &pieces {
+        commands.entity(piece).despawn();
+    }
+    board.clear();
+}
+
+/// What a move does besides moving the piece, used to pick its marker and to apply it
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MoveKind {
+    pub capture: bool,
+    pub promotion: Option<ChessPiece>,
+    /// where the rook moves from and to when castling
+    pub castle: Option<(Position, Position)>,
+    pub check: bool,
+}
+
+/// Every legal move `piece` can make from `position` along with what kind of move it is.
+/// Moves that leave one of `team`'s kings attacked are left out, so a king is never taken.
+pub fn classified_moves(
+    position: &Position,
+    piece: ChessPiece,
+    team: Team,
+    has_moved: bool,
+    board: &BoardState,
+    info: &Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+) -> Vec<(Position, MoveKind)> {
+    let lookup = |entity: Entity| info.get(entity).ok().map(|(p, t, _)| (*p, *t));
+    let safety = KingSafety::new(board, team, lookup);
+    classified_moves_with(position, piece, team, has_moved, board, info, &safety)
+}
+
+/// `classified_moves` for when every piece of `team` is looked at, so `safety` is only worked out once
+pub fn classified_moves_with(
+    position: &Position,
+    piece: ChessPiece,
+    team: Team,
+    has_moved: bool,
+    board: &BoardState,
+    info: &Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+    safety: &KingSafety,
+) -> Vec<(Position, MoveKind)> {
+    let lookup = |entity: Entity| info.get(entity).ok().map(|(p, t, _)| (*p, *t));
+    let mut moves = piece
+        .all_possible_moves(
+            position,
+            team,
+            board,
+            &info.clone().transmute_lens().query(),
+        )
+        .into_iter()
+        .filter(|to| !safety.enemy_kings.contains(to))
+        .map(|to| {
+            let kind = MoveKind {
+                capture: board.get(&to).is_some(),
+                promotion: special_moves::promotion(piece, team, &to, board.size()),
+                ..Default::default()
+            };
+            (to, kind)
+        })
+        .collect::<Vec<_>>();
+    if piece == ChessPiece::King && !has_moved {
+        moves.extend(
+            special_moves::castling_moves(position, team, board, info)
+                .into_iter()
+                .map(|(to, rook_from, rook_to)| {
+                    let kind = MoveKind {
+                        castle: Some((rook_from, rook_to)),
+                        ..Default::default()
+                    };
+                    (to, kind)
+                }),
+        );
+    }
+    moves.retain_mut(|(to, kind)| {
+        let mut moved = vec![(kind.promotion.unwrap_or(piece), &*to)];
+        let mut vacated = vec![position];
+        if let Some((rook_from, rook_to)) = &kind.castle {
+            moved.push((ChessPiece::Rook, rook_to));
+            vacated.push(rook_from);
+        }
+        if attack_map::exposes_king(board, team, &moved, &vacated, safety, lookup) {
+            return false;
+        }
+        kind.check =
+            attack_map::gives_check(board, team, &moved, &vacated, &safety.enemy_kings, lookup);
+        true
+    });
+    moves
+}
+
 #[derive(Resource)]
 struct PossibleMoveAssets {
     possible_move_material: Handle<StandardMaterial>,
+    capture_material: Handle<StandardMaterial>,
+    promotion_material: Handle<StandardMaterial>,
+    castle_material: Handle<StandardMaterial>,
+    check_material: Handle<StandardMaterial>,
     possible_move_mesh: Handle<Mesh>,
+    capture_mesh: Handle<Mesh>,
 }
 
 impl FromWorld for PossibleMoveAssets {
     fn from_world(world: &mut World) -> Self {
         let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
-        let possible_move_material = materials.add(StandardMaterial {
-            base_color: Color::linear_rgba(0.0, 1.0, 0.0, 0.5),
-            alpha_mode: AlphaMode::Blend,
-            ..Default::default()
-        });
+        let mut marker = |color: Color| {
+            materials.add(StandardMaterial {
+                base_color: color,
+                alpha_mode: AlphaMode::Blend,
+                ..Default::default()
+            })
+        };
+        let possible_move_material = marker(Color::linear_rgba(0.0, 1.0, 0.0, 0.5));
+        let capture_material = marker(Color::linear_rgba(1.0, 0.0, 0.0, 0.6));
+        let promotion_material = marker(Color::linear_rgba(0.6, 0.0, 1.0, 0.6));
+        let castle_material = marker(Color::linear_rgba(0.0, 0.4, 1.0, 0.6));
+        let check_material = marker(Color::linear_rgba(1.0, 0.5, 0.0, 0.8));
         let mut meshes = world.resource_mut::<Assets<Mesh>>();
         let mut mesh = Cylinder::new(0.2, 0.2)
             .mesh()
@@ -608,13 +928,47 @@ impl FromWorld for PossibleMoveAssets {
             Some(())
         });
         let possible_move_mesh = meshes.add(mesh);
+        // a ring floating above the piece that would be taken
+        let capture_mesh = meshes.add(
+            bevy::math::primitives::Torus::new(0.3, 0.45)
+                .mesh()
+                .build()
+                .translated_by(Vec3::Y * 1.1),
+        );
         Self {
             possible_move_material,
+            capture_material,
+            promotion_material,
+            castle_material,
+            check_material,
             possible_move_mesh,
+            capture_mesh,
         }
     }
 }
 
+impl PossibleMoveAssets {
+    fn marker(&self, kind: &MoveKind) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        let mesh = if kind.capture {
+            self.capture_mesh.clone()
+        } else {
+            self.possible_move_mesh.clone()
+        };
+        let material = if kind.check {
+            &self.check_material
+        } else if kind.promotion.is_some() {
+            &self.promotion_material
+        } else if kind.castle.is_some() {
+            &self.castle_material
+        } else if kind.capture {
+            &self.capture_material
+        } else {
+            &self.possible_move_material
+        };
+        (mesh, material.clone())
+    }
+}
+
 fn clean_up_possible_moves(
     removed: Query<(), With<Selected>>,
     old_moves: Populated<Entity, With<PossibleMove>>,
@@ -630,7 +984,8 @@ fn clean_up_possible_moves(
 fn display_possible_moves(
     selected: Trigger<OnAdd, Selected>,
     old_moves: Query<Entity, With<PossibleMove>>,
-    pieces: Query<(&Position, &ChessPiece, &Team)>,
+    pieces: Query<(&Position, &ChessPiece, &Team, Has<HasMoved>)>,
+    info: Query<(&ChessPiece, &Team, Has<HasMoved>)>,
     mut commands: Commands,
     assets: Res<PossibleMoveAssets>,
     board: Res<BoardState>,
@@ -638,36 +993,254 @@ fn display_possible_moves(
     for old in &old_moves {
         commands.entity(old).despawn();
     }
-    let (position, piece, team) = pieces
+    let (position, piece, team, has_moved) = pieces
         .get(selected.target())
         .expect("Just added Selected, must have piece");
-    for possible in piece.all_possible_moves(
-        position,
-        *team,
-        &board,
-        &pieces.clone().transmute_lens().query(),
-    ) {
+    for (possible, kind) in classified_moves(position, *piece, *team, has_moved, &board, &info) {
+        let (mesh, material) = assets.marker(&kind);
         commands.spawn((
             Name::new("Possible Move"),
             possible.clone(),
             PossibleMove,
-            Mesh3d(assets.possible_move_mesh.clone()),
-            MeshMaterial3d(assets.possible_move_material.clone()),
+            kind,
+            Mesh3d(mesh),
+            MeshMaterial3d(material),
         ));
     }
 }
 
-fn make_move(
+/// Sent after a piece has been moved and the turn has passed to the other side
+#[derive(Event, Debug, Clone)]
+pub struct MoveMade {
+    pub entity: Entity,
+    pub piece: ChessPiece,
+    pub team: Team,
+    pub from: Position,
+    pub to: Position,
+    pub captured: Option<ChessPiece>,
+    pub kind: MoveKind,
+    /// `position_key` of the position the move leaves, taken as it is played
+    pub key: u64,
+}
+
+/// Long algebraic notation with N-D cells, `Nb1.1-c3.2`, `e7xd8.1=Q+`
+impl std::fmt::Display for MoveMade {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = if self.captured.is_some() { 'x' } else { '-' };
+        write!(f, "{}{}{action}{}", self.piece.letter(), self.from, self.to)?;
+        if let Some(promotion) = self.kind.promotion {
+            write!(f, "={}", promotion.letter())?;
+        }
+        if self.kind.castle.is_some() {
+            write!(f, " O-O")?;
+        }
+        if self.kind.check {
+            write!(f, "+")?;
+        }
+        Ok(())
+    }
+}
+
+/// Ask for the selected piece to move to `to`, anything that isn't one of its possible moves is sent back as an `IllegalMove`
+/// and deselects the piece
+#[derive(Event, Debug, Clone)]
+pub struct RequestMove {
+    pub to: Position,
+}
+
+/// A move was asked for that the selected piece can't make
+#[derive(Event, Debug, Clone)]
+pub struct IllegalMove {
+    pub to: Position,
+}
+
+/// Makes the moves of a side no one plays here, the computer, an engine or the other end of a connection.
+/// The move goes through selection like a player's so the markers and animations are the same:
+/// the piece is selected one frame and moved once its possible moves have been spawned.
+#[derive(SystemParam)]
+pub struct PlannedMove<'w, 's> {
+    planned: Local<'s, Option<(Entity, Position)>>,
+    selected: Query<'w, 's, (), With<Selected>>,
+    markers: Query<'w, 's, (), With<PossibleMove>>,
+    animating: Query<'w, 's, (), With<MoveTween>>,
+    commands: Commands<'w, 's>,
+}
+
+impl PlannedMove<'_, '_> {
+    /// Carry on with the move being made, false once there is none and the next one can be picked.
+    /// The last move has to finish animating first so it can be followed.
+    pub fn busy(&mut self) -> bool {
+        if !self.animating.is_empty() {
+            return true;
+        }
+        let Some((piece, to)) = self.planned.take() else {
+            return false;
+        };
+        // the piece was deselected or taken under it, pick again
+        if !self.selected.contains(piece) {
+            return false;
+        }
+        if self.markers.is_empty() {
+            *self.planned = Some((piece, to));
+        } else {
+            self.commands.trigger(RequestMove { to });
+        }
+        true
+    }
+
+    /// Select `piece` to move it to `to` once its possible moves are there
+    pub fn start(&mut self, piece: Entity, to: Position) {
+        self.commands.entity(piece).insert(Selected);
+        *self.planned = Some((piece, to));
+    }
+
+    /// Forget the move being made, for when the side isn't played by this mover anymore
+    pub fn cancel(&mut self) {
+        *self.planned = None;
+    }
+}
+
+fn click_possible_move(
     trigger: Trigger<Pointer<Click>>,
-    mut selected: Single<(Entity, &mut Position), With<Selected>>,
-    can_move: Query<&Position, (With<PossibleMove>, Without<Selected>)>,
+    possible: Query<&Position, With<PossibleMove>>,
+    mut commands: Commands,
+) {
+    let Ok(to) = possible.get(trigger.target()) else {
+        return;
+    };
+    commands.trigger(RequestMove { to: to.clone() });
+}
+
+fn make_move(
+    trigger: Trigger<RequestMove>,
+    selected: Single<(Entity, &mut Position, &ChessPiece, &Team), With<Selected>>,
+    can_move: Query<(&Position, &MoveKind), (With<PossibleMove>, Without<Selected>)>,
+    pieces: Query<(&ChessPiece, &Team)>,
     mut commands: Commands,
     mut board: ResMut<BoardState>,
+    mut turn: ResMut<Team>,
+    mut moves: EventWriter<MoveMade>,
+    mut illegal: EventWriter<IllegalMove>,
 ) {
-    let Ok(move_to) = can_move.get(trigger.target()) else {
+    let Some((move_to, kind)) = can_move
+        .iter()
+        .find(|(position, _)| **position == trigger.to)
+    else {
+        illegal.write(IllegalMove {
+            to: trigger.to.clone(),
+        });
+        // pick again, a planned move that stays selected would be tried forever
+        commands.entity(selected.0).remove::<Selected>();
         return;
     };
-    board.move_piece(&selected.1, move_to);
-    *selected.1 = move_to.clone();
-    commands.entity(selected.0).remove::<Selected>();
+    let (entity, mut position, piece, team) = selected.into_inner();
+    commands.entity(entity).remove::<Selected>();
+    let made = play_move(
+        (entity, *piece, *team),
+        &mut position,
+        move_to,
+        kind,
+        &mut board,
+        &pieces,
+        &mut commands,
+    );
+    *turn = team.opposite();
+    moves.write(made);
+}
+
+/// Check `team` has a piece on `from` that can move to `to`, for moves that are sent rather then picked on the board.
+/// Returns the piece and the kind of move to give `play_move`.
+pub fn checked_move(
+    from: &Position,
+    to: &Position,
+    team: Team,
+    board: &BoardState,
+    info: &Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+) -> Result<(Entity, ChessPiece, MoveKind), String> {
+    let Some((entity, (piece, _, has_moved))) = board
+        .get(from)
+        .and_then(|entity| info.get(entity).ok().map(|info| (entity, info)))
+        .filter(|(_, (_, piece_team, _))| **piece_team == team)
+    else {
+        return Err(format!("{team:?} has no piece on {from}"));
+    };
+    classified_moves(from, *piece, team, has_moved, board, info)
+        .into_iter()
+        .find(|(cell, _)| cell == to)
+        .map(|(_, kind)| (entity, *piece, kind))
+        .ok_or_else(|| format!("{from} to {to} isn't a legal move for {team:?}"))
+}
+
+/// How the game ends when `team` has no move to make, `None` when it has one
+pub fn no_move_outcome(
+    team: Team,
+    board: &BoardState,
+    info: &Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+) -> Option<GameOutcome> {
+    let lookup = |entity: Entity| info.get(entity).ok().map(|(p, t, _)| (*p, *t));
+    let safety = KingSafety::new(board, team, lookup);
+    for (position, entity) in board.iter() {
+        let Ok((piece, piece_team, has_moved)) = info.get(entity) else {
+            continue;
+        };
+        if *piece_team == team
+            && !classified_moves_with(position, *piece, team, has_moved, board, info, &safety)
+                .is_empty()
+        {
+            return None;
+        }
+    }
+    let checked = safety.kings.iter().any(|(_, attacked)| *attacked);
+    Some(if checked {
+        GameOutcome::Checkmate(team)
+    } else {
+        GameOutcome::Draw(DrawReason::Stalemate)
+    })
+}
+
+/// Move a piece to `to`, which has to be one of its `classified_moves`, along with the rook if it castles.
+/// The turn isn't passed here, the returned move is sent as `MoveMade` once it has been.
+/// Its key is for the other side to move.
+pub fn play_move(
+    (entity, piece, team): (Entity, ChessPiece, Team),
+    position: &mut Position,
+    to: &Position,
+    kind: &MoveKind,
+    board: &mut BoardState,
+    pieces: &Query<(&ChessPiece, &Team)>,
+    commands: &mut Commands,
+) -> MoveMade {
+    let captured = board
+        .get(to)
+        .and_then(|entity| pieces.get(entity).ok())
+        .map(|(piece, _)| *piece);
+    let from = position.clone();
+    board.move_piece(&from, to);
+    *position = to.clone();
+    special_moves::apply(
+        entity,
+        kind.promotion,
+        kind.castle.as_ref(),
+        board,
+        commands,
+    );
+    // a promotion is only inserted once the commands are applied
+    let promoted = kind.promotion.unwrap_or(piece);
+    let key = position_key_with(board, team.opposite(), |other| {
+        if other == entity {
+            Some((promoted, team))
+        } else {
+            pieces.get(other).ok().map(|(piece, team)| (*piece, *team))
+        }
+    });
+    MoveMade {
+        entity,
+        piece,
+        team,
+        from,
+        to: to.clone(),
+        captured,
+        kind: kind.clone(),
+        key,
+    }
 }
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+
+use crate::board::{Position, RenderedAxes};
+use crate::pieces::ChessPiece;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MoveAnimation>()
+            .add_systems(Update, (tween_pieces, animate_captures));
+    }
+}
+
+/// How pieces move between cells, a `duration` of 0 teleports them like before
+#[derive(Resource)]
+pub struct MoveAnimation {
+    pub duration: f32,
+    /// height of the arc knights jump in
+    pub leap_height: f32,
+    /// keep the camera on pieces that jump across dimensions 3+
+    pub follow_camera: bool,
+    pub capture_duration: f32,
+}
+
+impl Default for MoveAnimation {
+    fn default() -> Self {
+        Self {
+            duration: 0.4,
+            leap_height: 1.5,
+            follow_camera: true,
+            capture_duration: 0.3,
+        }
+    }
+}
+
+/// Moves a piece from its old translation to its new one over `MoveAnimation::duration`
+#[derive(Component)]
+pub struct MoveTween {
+    start: Vec3,
+    end: Vec3,
+    elapsed: f32,
+    arc: f32,
+    /// the move changed an axis past the first 2D board, these can cover a long way in world space
+    pub crosses_dimensions: bool,
+}
+
+/// Set on a taken piece instead of despawning it straight away, it shrinks out of existence once
+/// the capturing piece has arrived
+#[derive(Component, Default)]
+pub struct Captured {
+    elapsed: f32,
+}
+
+pub(super) fn update_piece_position(
+    mut query: Query<
+        (
+            Entity,
+            &Position,
+            &mut Transform,
+            Option<&mut Visibility>,
+            Option<&ChessPiece>,
+            Option<&MoveTween>,
+        ),
+        Changed<Position>,
+    >,
+    settings: Res<MoveAnimation>,
+    axes: Res<RenderedAxes>,
+    mut removed: RemovedComponents<ChessPiece>,
+    mut commands: Commands,
+    mut last: Local<bevy::platform::collections::HashMap<Entity, Position>>,
+) {
+    for entity in removed.read() {
+        last.remove(&entity);
+    }
+    for (entity, position, mut transform, visibility, piece, tween) in query.iter_mut() {
+        if let Some(mut visibility) = visibility {
+            *visibility = if position.is_visible(&axes) {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+        }
+        let end = position.to_translation(&axes);
+        transform.scale = Vec3::splat(position.render_scale(&axes));
+        let Some(piece) = piece else {
+            transform.translation = end;
+            continue;
+        };
+        let previous = last.insert(entity, position.clone());
+        // pieces that were just spawned are placed, only moves slide
+        let Some(previous) = previous else {
+            transform.translation = end;
+            continue;
+        };
+        if settings.duration <= 0. {
+            transform.translation = end;
+            continue;
+        }
+        let start = tween.map_or(transform.translation, |tween| tween.end);
+        transform.translation = start;
+        let arc = if *piece == ChessPiece::Knight {
+            settings.leap_height
+        } else {
+            0.
+        };
+        let crosses_dimensions = previous
+            .iter()
+            .zip(position.iter())
+            .skip(2)
+            .any(|(a, b)| a != b);
+        commands.entity(entity).insert(MoveTween {
+            start,
+            end,
+            elapsed: 0.,
+            arc,
+            crosses_dimensions,
+        });
+    }
+}
+
+fn tween_pieces(
+    mut pieces: Query<(Entity, &mut Transform, &mut MoveTween)>,
+    time: Res<Time>,
+    settings: Res<MoveAnimation>,
+    mut commands: Commands,
+) {
+    for (entity, mut transform, mut tween) in &mut pieces {
+        tween.elapsed += time.delta_secs();
+        let t = (tween.elapsed / settings.duration).clamp(0., 1.);
+        let eased = t * t * (3. - 2. * t);
+        transform.translation =
+            tween.start.lerp(tween.end, eased) + Vec3::Y * tween.arc * 4. * t * (1. - t);
+        if t >= 1. {
+            commands.entity(entity).remove::<MoveTween>();
+        }
+    }
+}
+
+fn animate_captures(
+    mut captured: Query<(Entity, &mut Transform, &mut Captured)>,
+    time: Res<Time>,
+    settings: Res<MoveAnimation>,
+    mut commands: Commands,
+) {
+    for (entity, mut transform, mut capture) in &mut captured {
+        capture.elapsed += time.delta_secs();
+        // wait for the capturing piece to land before shrinking
+        let t = ((capture.elapsed - settings.duration) / settings.capture_duration).clamp(0., 1.);
+        transform.scale = Vec3::splat(1. - t);
+        if capture.elapsed >= settings.duration + settings.capture_duration {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,520 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::board::{
+    BoardState, DimensionIter, MAX_REACH, NewPositionIter, OnCell, Position, PositionIter,
+    RenderedAxes, WithOffset,
+};
+use crate::pieces::move_iterators::BishopMoveIterator;
+use crate::pieces::{ChessPiece, Team};
+
+pub struct AttackMapPlugin;
+
+impl Plugin for AttackMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AttackOverlay>()
+            .init_resource::<AttackOverlayAssets>()
+            .add_systems(
+                Update,
+                (toggle_attack_overlay, update_attack_overlay).chain(),
+            );
+    }
+}
+
+/// Colours every attacked cell by how many white (blue) and black (red) pieces attack it,
+/// pieces that would lose material in an exchange are shown in yellow
+#[derive(Resource)]
+pub struct AttackOverlay {
+    pub enabled: bool,
+    pub toggle: KeyCode,
+}
+
+impl Default for AttackOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            toggle: KeyCode::KeyT,
+        }
+    }
+}
+
+/// Every piece attacking each cell, from both teams
+#[derive(Default)]
+pub struct AttackMap {
+    cells: HashMap<Position, Vec<(ChessPiece, Team)>>,
+}
+
+impl AttackMap {
+    pub fn new(board: &BoardState, pieces: &Query<(&ChessPiece, &Team)>) -> Self {
+        let mut map = Self::default();
+        for (position, entity) in board.iter() {
+            let Ok((piece, team)) = pieces.get(entity) else {
+                continue;
+            };
+            for cell in piece.attacked_cells(position, *team, board) {
+                map.cells.entry(cell).or_default().push((*piece, *team));
+            }
+        }
+        map
+    }
+
+    pub fn attackers(&self, cell: &Position, team: Team) -> impl Iterator<Item = ChessPiece> {
+        self.cells
+            .get(cell)
+            .into_iter()
+            .flatten()
+            .filter(move |(_, t)| *t == team)
+            .map(|(piece, _)| *piece)
+    }
+
+    pub fn count(&self, cell: &Position, team: Team) -> usize {
+        self.attackers(cell, team).count()
+    }
+
+    /// Material `by` expects to win by starting an exchange on `cell` against `target`
+    pub fn static_exchange(&self, cell: &Position, target: ChessPiece, by: Team) -> i32 {
+        let mut attackers = self
+            .attackers(cell, by)
+            .map(|p| p.value())
+            .collect::<Vec<_>>();
+        let mut defenders = self
+            .attackers(cell, by.opposite())
+            .map(|p| p.value())
+            .collect::<Vec<_>>();
+        attackers.sort_unstable();
+        defenders.sort_unstable();
+        static_exchange(target.value(), &attackers, &defenders)
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = &Position> {
+        self.cells.keys()
+    }
+}
+
+/// Swap list evaluation, attackers and defenders must be sorted cheapest first
+pub fn static_exchange(target: i32, attackers: &[i32], defenders: &[i32]) -> i32 {
+    let Some(&first) = attackers.first() else {
+        return 0;
+    };
+    let sides = [attackers, defenders];
+    let mut next = [1, 0];
+    let mut side = 1;
+    let mut on_square = first;
+    let mut gains = vec![target];
+    while let Some(&piece) = sides[side].get(next[side]) {
+        next[side] += 1;
+        let last = *gains.last().expect("gains starts with the target");
+        gains.push(on_square - last);
+        on_square = piece;
+        side ^= 1;
+    }
+    while gains.len() > 1 {
+        let last = gains.pop().expect("len > 1");
+        let previous = gains.last_mut().expect("len > 1");
+        *previous = -(-*previous).max(last);
+    }
+    gains[0]
+}
+
+/// Where every knight of `team` is, generating knight moves gets slow in high dimensions so
+/// `is_cell_attacked` checks each knight's offset instead
+pub fn knights(
+    board: &BoardState,
+    team: Team,
+    lookup: impl Fn(Entity) -> Option<(ChessPiece, Team)>,
+) -> Vec<Position> {
+    board
+        .iter()
+        .filter(|(_, entity)| lookup(*entity) == Some((ChessPiece::Knight, team)))
+        .map(|(position, _)| position.clone())
+        .collect()
+}
+
+/// A knight moves 1 along one axis, 2 along another and so on for at least 2 axes,
+/// matching what `KnightMoveIterator` generates
+pub fn is_knight_offset(from: &Position, to: &Position) -> bool {
+    let mut steps = from
+        .iter()
+        .zip(to.iter())
+        .map(|(a, b)| (b - a).unsigned_abs())
+        .filter(|step| *step != 0)
+        .collect::<Vec<_>>();
+    steps.sort_unstable();
+    steps.len() >= 2
+        && steps
+            .iter()
+            .enumerate()
+            .all(|(i, &step)| step as usize == i + 1)
+}
+
+/// Whether any piece of team `by` attacks `cell`, walks outwards from the cell instead of building a full `AttackMap`
+pub fn is_cell_attacked(
+    board: &BoardState,
+    cell: &Position,
+    by: Team,
+    knights: &[Position],
+    lookup: impl Fn(Entity) -> Option<(ChessPiece, Team)>,
+) -> bool {
+    attacked_with(cell, by, knights, board.size(), |position| {
+        board.get(position).and_then(&lookup)
+    })
+}
+
+/// `is_cell_attacked` reading the board through `piece_at`, so a move can be looked at without copying the board
+fn attacked_with(
+    cell: &Position,
+    by: Team,
+    knights: &[Position],
+    size: i8,
+    piece_at: impl Fn(&Position) -> Option<(ChessPiece, Team)>,
+) -> bool {
+    let dimensions = cell.len();
+    let first_hit = |line: &mut dyn Iterator<Item = Position>| {
+        line.filter(|next| next != cell)
+            .find_map(|next| piece_at(&next))
+    };
+    for axis in 0..dimensions {
+        for up in [true, false] {
+            if let Some((piece, team)) = first_hit(
+                &mut DimensionIter::<MAX_REACH>::new(dimensions, axis, up).with_offset(cell, size),
+            ) && team == by
+                && matches!(piece, ChessPiece::Rook | ChessPiece::Queen)
+            {
+                return true;
+            }
+        }
+    }
+    for diagonal in BishopMoveIterator::new(dimensions) {
+        if let Some((piece, team)) = first_hit(&mut diagonal.with_offset(cell, size))
+            && team == by
+            && matches!(piece, ChessPiece::Bishop | ChessPiece::Queen)
+        {
+            return true;
+        }
+    }
+    if knights.iter().any(|knight| is_knight_offset(knight, cell)) {
+        return true;
+    }
+    for next in NewPositionIter::<2>::new(dimensions).with_offset(&cell.dec_all(), size) {
+        if next != *cell && piece_at(&next) == Some((ChessPiece::King, by)) {
+            return true;
+        }
+    }
+    // pawns attack one forward on axis 0 and one along any other axis
+    let behind = if by == Team::White {
+        cell.clone().dec(0)
+    } else {
+        cell.clone().inc(0)
+    };
+    for next in PositionIter::<1>::start_at(dimensions, 1).with_offset(&behind, size) {
+        if next != behind && piece_at(&next) == Some((ChessPiece::Pawn, by)) {
+            return true;
+        }
+    }
+    false
+}
+
+/// What `classified_moves` needs to know about the position to keep `team`'s kings safe,
+/// worked out once per position rather then once per piece
+pub struct KingSafety {
+    /// `team`'s kings and whether each is attacked before the move
+    pub kings: Vec<(Position, bool)>,
+    pub enemy_kings: Vec<Position>,
+    /// the other team's knights
+    pub knights: Vec<Position>,
+}
+
+impl KingSafety {
+    pub fn new(
+        board: &BoardState,
+        team: Team,
+        lookup: impl Fn(Entity) -> Option<(ChessPiece, Team)>,
+    ) -> Self {
+        let kings_of = |team: Team| {
+            board
+                .iter()
+                .filter(|(_, entity)| lookup(*entity) == Some((ChessPiece::King, team)))
+                .map(|(position, _)| position.clone())
+                .collect::<Vec<_>>()
+        };
+        let knights = knights(board, team.opposite(), &lookup);
+        let kings = kings_of(team)
+            .into_iter()
+            .map(|king| {
+                let attacked = is_cell_attacked(board, &king, team.opposite(), &knights, &lookup);
+                (king, attacked)
+            })
+            .collect();
+        Self {
+            kings,
+            enemy_kings: kings_of(team.opposite()),
+            knights,
+        }
+    }
+}
+
+/// Whether moving `moved` pieces onto their cells leaves one of `team`'s kings attacked.
+/// Read the same way as `gives_check`, a king that stays put only needs looking at again when it was attacked or a cell on one of its lines is vacated.
+pub fn exposes_king(
+    board: &BoardState,
+    team: Team,
+    moved: &[(ChessPiece, &Position)],
+    vacated: &[&Position],
+    safety: &KingSafety,
+    lookup: impl Fn(Entity) -> Option<(ChessPiece, Team)>,
+) -> bool {
+    let piece_at = |cell: &Position| {
+        if let Some((piece, _)) = moved.iter().find(|(_, to)| *to == cell) {
+            return Some((*piece, team));
+        }
+        if vacated.contains(&cell) {
+            return None;
+        }
+        board.get(cell).and_then(&lookup)
+    };
+    // a knight that is taken doesn't attack anything
+    let knights = safety
+        .knights
+        .iter()
+        .filter(|knight| moved.iter().all(|(_, to)| to != knight))
+        .cloned()
+        .collect::<Vec<_>>();
+    let moved_kings = moved
+        .iter()
+        .filter(|(piece, _)| *piece == ChessPiece::King)
+        .map(|(_, to)| *to);
+    let staying = safety.kings.iter().filter(|(king, attacked)| {
+        !vacated.contains(&king)
+            && (*attacked
+                || vacated
+                    .iter()
+                    .any(|from| line_between(king, from).is_some()))
+    });
+    moved_kings
+        .chain(staying.map(|(king, _)| king))
+        .any(|king| attacked_with(king, team.opposite(), &knights, board.size(), piece_at))
+}
+
+/// Whether moving `moved` pieces onto their cells attacks any of `enemy_kings`, either directly or by
+/// uncovering a slider behind one of the `vacated` cells. `board` is from before the move,
+/// the cells the move changes are read as they will be after it so the board isn't copied for every move.
+pub fn gives_check(
+    board: &BoardState,
+    team: Team,
+    moved: &[(ChessPiece, &Position)],
+    vacated: &[&Position],
+    enemy_kings: &[Position],
+    lookup: impl Fn(Entity) -> Option<(ChessPiece, Team)>,
+) -> bool {
+    let piece_at = |cell: &Position| {
+        if let Some((piece, _)) = moved.iter().find(|(_, to)| *to == cell) {
+            return Some((*piece, team));
+        }
+        if vacated.contains(&cell) {
+            return None;
+        }
+        board.get(cell).and_then(&lookup)
+    };
+    // the first cell with a piece on it going from `from` in `direction`
+    let first_hit = |from: &Position, direction: &Position| {
+        let mut next = from.clone();
+        next.add(direction);
+        while board.contains(&next) {
+            if let Some(hit) = piece_at(&next) {
+                return Some((next, hit));
+            }
+            next.add(direction);
+        }
+        None
+    };
+    for (piece, to) in moved {
+        let direct = match piece {
+            ChessPiece::Rook | ChessPiece::Bishop | ChessPiece::Queen => {
+                enemy_kings.iter().any(|king| {
+                    line_between(to, king).is_some_and(|(direction, diagonal)| {
+                        slides_along(*piece, diagonal)
+                            && first_hit(to, &direction).is_some_and(|(cell, _)| cell == *king)
+                    })
+                })
+            }
+            // nothing can get in the way of the other pieces, so the board doesn't matter
+            _ => enemy_kings
+                .iter()
+                .any(|king| steps_onto(*piece, team, to, king)),
+        };
+        if direct {
+            return true;
+        }
+    }
+    for king in enemy_kings {
+        for from in vacated {
+            let Some((direction, diagonal)) = line_between(king, from) else {
+                continue;
+            };
+            if let Some((_, (piece, other))) = first_hit(king, &direction)
+                && other == team
+                && slides_along(piece, diagonal)
+            {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Whether a pawn, knight or king of `team` on `from` attacks `to`, without generating every cell it attacks
+fn steps_onto(piece: ChessPiece, team: Team, from: &Position, to: &Position) -> bool {
+    let delta = from
+        .iter()
+        .zip(to.iter())
+        .map(|(a, b)| b - a)
+        .collect::<Vec<_>>();
+    match piece {
+        ChessPiece::Knight => is_knight_offset(from, to),
+        ChessPiece::King => from != to && delta.iter().all(|d| d.abs() <= 1),
+        // one forward on axis 0 and one along any other axis
+        ChessPiece::Pawn => {
+            let forward = if team == Team::White { 1 } else { -1 };
+            delta[0] == forward
+                && delta[1..].iter().filter(|d| **d != 0).count() == 1
+                && delta[1..].iter().all(|d| d.abs() <= 1)
+        }
+        _ => false,
+    }
+}
+
+fn slides_along(piece: ChessPiece, diagonal: bool) -> bool {
+    piece == ChessPiece::Queen
+        || (diagonal && piece == ChessPiece::Bishop)
+        || (!diagonal && piece == ChessPiece::Rook)
+}
+
+/// The unit step from `from` towards `to` if they share a rook line or bishop diagonal,
+/// bishops move along every axis at once so a diagonal needs every axis to differ by the same amount
+fn line_between(from: &Position, to: &Position) -> Option<(Position, bool)> {
+    let delta = from
+        .iter()
+        .zip(to.iter())
+        .map(|(a, b)| b - a)
+        .collect::<Vec<_>>();
+    let moving = delta.iter().filter(|d| **d != 0).count();
+    let distance = delta.iter().map(|d| d.abs()).max()?;
+    if distance == 0 {
+        return None;
+    }
+    let diagonal = if moving == 1 {
+        false
+    } else if moving == delta.len() && delta.iter().all(|d| d.abs() == distance) {
+        true
+    } else {
+        return None;
+    };
+    Some((
+        Position(delta.iter().map(|d| d.signum()).collect()),
+        diagonal,
+    ))
+}
+
+#[derive(Component)]
+struct AttackOverlayCell;
+
+#[derive(Resource)]
+struct AttackOverlayAssets {
+    mesh: Handle<Mesh>,
+    hanging: Handle<StandardMaterial>,
+    materials: HashMap<(usize, usize), Handle<StandardMaterial>>,
+}
+
+impl FromWorld for AttackOverlayAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Plane3d::new(Vec3::Y, Vec2::splat(0.5)));
+        let hanging = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(overlay_material(Color::linear_rgba(1.0, 1.0, 0.0, 0.6)));
+        Self {
+            mesh,
+            hanging,
+            materials: HashMap::default(),
+        }
+    }
+}
+
+fn overlay_material(color: Color) -> StandardMaterial {
+    StandardMaterial {
+        base_color: color,
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..Default::default()
+    }
+}
+
+impl AttackOverlayAssets {
+    fn material(
+        &mut self,
+        materials: &mut Assets<StandardMaterial>,
+        white: usize,
+        black: usize,
+    ) -> Handle<StandardMaterial> {
+        // past 4 attackers the tint is already saturated
+        let key = (white.min(4), black.min(4));
+        self.materials
+            .entry(key)
+            .or_insert_with(|| {
+                let color = Color::linear_rgba(key.1 as f32 / 4., 0.0, key.0 as f32 / 4., 0.45);
+                materials.add(overlay_material(color))
+            })
+            .clone()
+    }
+}
+
+fn toggle_attack_overlay(mut overlay: ResMut<AttackOverlay>, input: Res<ButtonInput<KeyCode>>) {
+    if input.just_pressed(overlay.toggle) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+fn update_attack_overlay(
+    mut commands: Commands,
+    overlay: Res<AttackOverlay>,
+    board: Res<BoardState>,
+    axes: Res<RenderedAxes>,
+    pieces: Query<(&ChessPiece, &Team)>,
+    old_cells: Query<Entity, With<AttackOverlayCell>>,
+    mut assets: ResMut<AttackOverlayAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !overlay.is_changed() && !board.is_changed() && !axes.is_changed() {
+        return;
+    }
+    for old in &old_cells {
+        commands.entity(old).despawn();
+    }
+    if !overlay.enabled {
+        return;
+    }
+    let map = AttackMap::new(&board, &pieces);
+    for cell in map.cells().filter(|cell| cell.is_visible(&axes)) {
+        let hanging = board
+            .get(cell)
+            .and_then(|entity| pieces.get(entity).ok())
+            .is_some_and(|(piece, team)| map.static_exchange(cell, *piece, team.opposite()) > 0);
+        let material = if hanging {
+            assets.hanging.clone()
+        } else {
+            let white = map.count(cell, Team::White);
+            let black = map.count(cell, Team::Black);
+            assets.material(&mut materials, white, black)
+        };
+        commands.spawn((
+            Name::new("Attack Overlay"),
+            AttackOverlayCell,
+            OnCell(cell.clone()),
+            Mesh3d(assets.mesh.clone()),
+            MeshMaterial3d(material),
+            Transform::from_translation(cell.to_translation(&axes) + Vec3::Y * 0.01),
+            Pickable::IGNORE,
+        ));
+    }
+}
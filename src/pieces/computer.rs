@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+
+use crate::board::{BoardState, Position};
+use crate::game::{AppState, GameConfig, PlayerKind};
+use crate::network::Network;
+use crate::pieces::{
+    ChessPiece, HasMoved, KingSafety, MoveKind, PlannedMove, Team, classified_moves_with,
+};
+use crate::rules::MoveHistory;
+
+/// Plays the sides the game config gives to the computer
+pub struct ComputerPlugin;
+
+impl Plugin for ComputerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, computer_turn.run_if(in_state(AppState::Playing)));
+    }
+}
+
+/// Plays the move `choose_move` picks.
+fn computer_turn(
+    config: Res<GameConfig>,
+    network: Res<Network>,
+    turn: Res<Team>,
+    board: Res<BoardState>,
+    history: Res<MoveHistory>,
+    pieces: Query<(Entity, &ChessPiece, &Team, &Position)>,
+    info: Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+    mut planned: PlannedMove,
+) {
+    if network.player(&config, *turn) != PlayerKind::Computer {
+        planned.cancel();
+        return;
+    }
+    if planned.busy() {
+        return;
+    }
+    let Some((piece, to)) = choose_move(*turn, &board, &pieces, &info, history.moves.len()) else {
+        return;
+    };
+    planned.start(piece, to);
+}
+
+/// Takes the most valuable piece it can, preferring promotions, and otherwise makes any move.
+/// `variation` picks between equally good moves, anything that changes from turn to turn like the number of moves so far.
+pub fn choose_move(
+    turn: Team,
+    board: &BoardState,
+    pieces: &Query<(Entity, &ChessPiece, &Team, &Position)>,
+    info: &Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+    variation: usize,
+) -> Option<(Entity, Position)> {
+    let value = |to: &Position, kind: &MoveKind| {
+        let captured = board
+            .get(to)
+            .and_then(|entity| pieces.get(entity).ok())
+            .map_or(0, |(_, captured, ..)| captured.value());
+        captured * 10 + kind.promotion.is_some() as i32 * 80
+    };
+    let lookup = |entity: Entity| info.get(entity).ok().map(|(p, t, _)| (*p, *t));
+    let safety = KingSafety::new(board, turn, lookup);
+    let mut best = Vec::new();
+    let mut best_value = i32::MIN;
+    for (entity, piece, team, position) in pieces {
+        if *team != turn {
+            continue;
+        }
+        let has_moved = info.get(entity).is_ok_and(|(.., has_moved)| has_moved);
+        for (to, kind) in
+            classified_moves_with(position, *piece, *team, has_moved, board, info, &safety)
+        {
+            let value = value(&to, &kind);
+            if value > best_value {
+                best_value = value;
+                best.clear();
+            }
+            if value == best_value {
+                best.push((entity, to));
+            }
+        }
+    }
+    if best.is_empty() {
+        return None;
+    }
+    // vary the quiet moves between turns without pulling in a random number generator
+    Some(best.swap_remove(variation.wrapping_mul(7919) % best.len()))
+}
@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+
+use crate::board::{BoardState, Position, RenderedAxes};
+use crate::game::{AppState, GameConfig, InGame};
+use crate::network::Network;
+use crate::pieces::{ChessPiece, RequestMove, Selected, Team, can_move_team};
+use crate::rules::GameOutcome;
+
+/// A cell cursor driven from the keyboard, for playing without the mouse
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CursorBindings>()
+            .init_resource::<CellCursor>()
+            .add_systems(OnExit(InGame), reset_cursor)
+            .add_systems(
+                Update,
+                (move_cursor, confirm_cursor, draw_cursor)
+                    .chain()
+                    .run_if(in_state(AppState::Playing)),
+            );
+    }
+}
+
+/// The cell the keyboard cursor is on and which axis the arrows move it along
+#[derive(Resource, Default)]
+pub struct CellCursor {
+    /// `None` until a cursor key is pressed, so mouse players don't see it
+    pub position: Option<Position>,
+    pub axis: usize,
+}
+
+/// Hold `modifier` and Up/Down moves along the active axis, Left/Right picks the active axis.
+/// Each of `axis_pairs` moves along its own axis without the modifier, `(forward, back)` for axis 0 first.
+#[derive(Resource)]
+pub struct CursorBindings {
+    pub modifier: [KeyCode; 2],
+    pub axis_pairs: Vec<(KeyCode, KeyCode)>,
+    pub confirm: KeyCode,
+}
+
+impl Default for CursorBindings {
+    fn default() -> Self {
+        Self {
+            modifier: [KeyCode::ControlLeft, KeyCode::ControlRight],
+            axis_pairs: vec![
+                (KeyCode::Numpad8, KeyCode::Numpad2),
+                (KeyCode::Numpad6, KeyCode::Numpad4),
+                (KeyCode::Numpad9, KeyCode::Numpad1),
+                (KeyCode::Numpad3, KeyCode::Numpad7),
+                (KeyCode::NumpadAdd, KeyCode::NumpadSubtract),
+                (KeyCode::NumpadMultiply, KeyCode::NumpadDivide),
+            ],
+            confirm: KeyCode::Space,
+        }
+    }
+}
+
+impl CursorBindings {
+    /// The arrow keys belong to the cursor while the modifier is held
+    pub fn modifier_held(&self, input: &ButtonInput<KeyCode>) -> bool {
+        input.any_pressed(self.modifier)
+    }
+}
+
+/// The next game may have a different number of axes
+fn reset_cursor(mut cursor: ResMut<CellCursor>) {
+    *cursor = CellCursor::default();
+}
+
+fn move_cursor(
+    input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<CursorBindings>,
+    mut cursor: ResMut<CellCursor>,
+    mut axes: ResMut<RenderedAxes>,
+) {
+    let dimensions = axes.dimensions();
+    let mut steps = Vec::new();
+    if bindings.modifier_held(&input) {
+        if input.just_pressed(KeyCode::ArrowRight) {
+            cursor.axis = (cursor.axis + 1) % dimensions;
+            info!("Cursor moves along axis {}", cursor.axis);
+        }
+        if input.just_pressed(KeyCode::ArrowLeft) {
+            cursor.axis = (cursor.axis + dimensions - 1) % dimensions;
+            info!("Cursor moves along axis {}", cursor.axis);
+        }
+        if input.just_pressed(KeyCode::ArrowUp) {
+            steps.push((cursor.axis, 1));
+        }
+        if input.just_pressed(KeyCode::ArrowDown) {
+            steps.push((cursor.axis, -1));
+        }
+    }
+    for (axis, &(forward, back)) in bindings.axis_pairs.iter().enumerate().take(dimensions) {
+        if input.just_pressed(forward) {
+            steps.push((axis, 1));
+        }
+        if input.just_pressed(back) {
+            steps.push((axis, -1));
+        }
+    }
+    if steps.is_empty() {
+        return;
+    }
+    let position = cursor
+        .position
+        .get_or_insert_with(|| Position(vec![0; dimensions]));
+    for (axis, by) in steps {
+        let Some(index) = position.0.get_mut(axis) else {
+            continue;
+        };
+        *index = (*index + by).clamp(0, axes.size - 1);
+        // follow the cursor onto the slice it moved to
+        if !axes.is_spatial(axis) && axes.slice[axis] != *index {
+            axes.slice[axis] = *index;
+        }
+    }
+}
+
+/// Confirm selects the piece under the cursor, or moves the selected piece to the cursor
+fn confirm_cursor(
+    input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<CursorBindings>,
+    cursor: Res<CellCursor>,
+    board: Res<BoardState>,
+    can_select: Query<&Team, With<ChessPiece>>,
+    selected: Query<(Entity, &Team), With<Selected>>,
+    turn: Res<Team>,
+    config: Res<GameConfig>,
+    network: Res<Network>,
+    outcome: Option<Res<GameOutcome>>,
+    mut commands: Commands,
+) {
+    if !input.just_pressed(bindings.confirm) || outcome.is_some() {
+        return;
+    }
+    let Some(cell) = &cursor.position else {
+        return;
+    };
+    let mover = selected.iter().next().map(|(_, team)| *team);
+    if let Some(piece) = board.get(cell)
+        && let Ok(team) = can_select.get(piece)
+        && mover.is_none_or(|mover| mover == *team)
+        && can_move_team(*team, *turn, &config, &network)
+    {
+        if selected.contains(piece) {
+            commands.entity(piece).remove::<Selected>();
+        } else {
+            commands.entity(piece).insert(Selected);
+        }
+        return;
+    }
+    if mover.is_some_and(|mover| can_move_team(mover, *turn, &config, &network)) {
+        commands.trigger(RequestMove { to: cell.clone() });
+    }
+}
+
+fn draw_cursor(cursor: Res<CellCursor>, axes: Res<RenderedAxes>, mut gizmos: Gizmos) {
+    let Some(cell) = &cursor.position else {
+        return;
+    };
+    if !cell.is_visible(&axes) {
+        return;
+    }
+    let size = Vec2::splat(cell.render_scale(&axes) * 0.95);
+    gizmos.rect(
+        Isometry3d::new(
+            cell.to_translation(&axes) + Vec3::Y * 0.02,
+            Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+        ),
+        size,
+        Color::linear_rgb(0.0, 0.9, 1.0),
+    );
+}
@@ -0,0 +1,298 @@
+use bevy::prelude::*;
+
+use crate::board::{BoardState, OnCell, Position, RenderedAxes};
+use crate::game::InGame;
+use crate::pieces::attack_map;
+use crate::pieces::{ChessPiece, IllegalMove, MoveMade, Team};
+
+pub struct IndicatorsPlugin;
+
+impl Plugin for IndicatorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastMove>()
+            .init_resource::<IndicatorAssets>()
+            .add_systems(Startup, spawn_last_move_highlights)
+            .add_systems(OnExit(InGame), clear_last_move)
+            .add_systems(
+                Update,
+                (
+                    (
+                        update_last_move,
+                        update_in_check,
+                        place_last_move_highlights,
+                    )
+                        .chain(),
+                    animate_check_indicators,
+                    draw_move_trail,
+                    (flash_illegal_moves, fade_illegal_move_flashes).chain(),
+                ),
+            )
+            .add_observer(add_check_indicator)
+            .add_observer(remove_check_indicator);
+    }
+}
+
+/// The cells the last move went from and to
+#[derive(Resource, Default)]
+pub struct LastMove(pub Option<(Position, Position)>);
+
+impl LastMove {
+    /// Moves along axis 0 and 1 stay on the same 2D board, anything else jumps between boards
+    pub fn crosses_dimensions(&self) -> bool {
+        let Some((from, to)) = &self.0 else {
+            return false;
+        };
+        from.iter()
+            .zip(to.iter())
+            .skip(2)
+            .any(|(from, to)| from != to)
+    }
+}
+
+/// Added to kings that are currently attacked
+#[derive(Component)]
+pub struct InCheck;
+
+#[derive(Component)]
+struct LastMoveHighlight {
+    to: bool,
+}
+
+#[derive(Component)]
+struct CheckIndicator;
+
+/// Flashed on a cell the selected piece can't move to
+#[derive(Component)]
+struct IllegalMoveFlash {
+    elapsed: f32,
+    scale: f32,
+}
+
+#[derive(Resource)]
+struct IndicatorAssets {
+    cell_mesh: Handle<Mesh>,
+    from_material: Handle<StandardMaterial>,
+    to_material: Handle<StandardMaterial>,
+    check_mesh: Handle<Mesh>,
+    check_material: Handle<StandardMaterial>,
+    illegal_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for IndicatorAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let cell_mesh = meshes.add(Plane3d::new(Vec3::Y, Vec2::splat(0.5)));
+        let check_mesh = meshes.add(bevy::math::primitives::Torus::new(0.5, 0.6));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let mut highlight = |color: Color| {
+            materials.add(StandardMaterial {
+                base_color: color,
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..Default::default()
+            })
+        };
+        let from_material = highlight(Color::linear_rgba(1.0, 0.8, 0.0, 0.35));
+        let to_material = highlight(Color::linear_rgba(1.0, 0.8, 0.0, 0.6));
+        let check_material = highlight(Color::linear_rgba(1.0, 0.0, 0.0, 0.8));
+        let illegal_material = highlight(Color::linear_rgba(1.0, 0.1, 0.1, 0.6));
+        Self {
+            cell_mesh,
+            from_material,
+            to_material,
+            check_mesh,
+            check_material,
+            illegal_material,
+        }
+    }
+}
+
+fn spawn_last_move_highlights(mut commands: Commands, assets: Res<IndicatorAssets>) {
+    for to in [false, true] {
+        let material = if to {
+            assets.to_material.clone()
+        } else {
+            assets.from_material.clone()
+        };
+        commands.spawn((
+            Name::new("Last Move Highlight"),
+            LastMoveHighlight { to },
+            Mesh3d(assets.cell_mesh.clone()),
+            MeshMaterial3d(material),
+            Transform::default(),
+            Visibility::Hidden,
+            Pickable::IGNORE,
+        ));
+    }
+}
+
+fn clear_last_move(mut last_move: ResMut<LastMove>) {
+    last_move.0 = None;
+}
+
+fn update_last_move(mut moves: EventReader<MoveMade>, mut last_move: ResMut<LastMove>) {
+    let Some(made) = moves.read().last() else {
+        return;
+    };
+    last_move.0 = Some((made.from.clone(), made.to.clone()));
+}
+
+fn place_last_move_highlights(
+    last_move: Res<LastMove>,
+    axes: Res<RenderedAxes>,
+    mut highlights: Query<(Entity, &LastMoveHighlight, &mut Transform, &mut Visibility)>,
+    mut commands: Commands,
+) {
+    if !last_move.is_changed() && !axes.is_changed() {
+        return;
+    }
+    let Some((from, to)) = &last_move.0 else {
+        for (.., mut visibility) in &mut highlights {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    };
+    for (entity, highlight, mut transform, mut visibility) in &mut highlights {
+        let cell = if highlight.to { to } else { from };
+        commands.entity(entity).insert(OnCell(cell.clone()));
+        // sit just above the board so it doesn't z-fight with the cells
+        transform.translation = cell.to_translation(&axes) + Vec3::Y * 0.005;
+        *visibility = if cell.is_visible(&axes) {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn update_in_check(
+    mut moves: EventReader<MoveMade>,
+    board: Res<BoardState>,
+    pieces: Query<(&ChessPiece, &Team)>,
+    in_check: Query<Entity, With<InCheck>>,
+    mut commands: Commands,
+) {
+    if moves.read().last().is_none() {
+        return;
+    }
+    let lookup = |entity: Entity| pieces.get(entity).ok().map(|(p, t)| (*p, *t));
+    let white_knights = attack_map::knights(&board, Team::White, lookup);
+    let black_knights = attack_map::knights(&board, Team::Black, lookup);
+    for (position, entity) in board.iter() {
+        let Some((ChessPiece::King, team)) = lookup(entity) else {
+            continue;
+        };
+        let knights = match team {
+            Team::White => &black_knights,
+            Team::Black => &white_knights,
+        };
+        let attacked =
+            attack_map::is_cell_attacked(&board, position, team.opposite(), knights, lookup);
+        match (attacked, in_check.contains(entity)) {
+            (true, false) => {
+                commands.entity(entity).insert(InCheck);
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<InCheck>();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn add_check_indicator(
+    trigger: Trigger<OnAdd, InCheck>,
+    mut commands: Commands,
+    assets: Res<IndicatorAssets>,
+) {
+    commands.spawn((
+        Name::new("Check Indicator"),
+        CheckIndicator,
+        Mesh3d(assets.check_mesh.clone()),
+        MeshMaterial3d(assets.check_material.clone()),
+        Transform::default(),
+        Pickable::IGNORE,
+        ChildOf(trigger.target()),
+    ));
+}
+
+fn remove_check_indicator(
+    trigger: Trigger<OnRemove, InCheck>,
+    children: Query<&Children>,
+    indicators: Query<(), With<CheckIndicator>>,
+    mut commands: Commands,
+) {
+    let Ok(children) = children.get(trigger.target()) else {
+        return;
+    };
+    for child in children.iter() {
+        if indicators.contains(child) {
+            commands.entity(child).despawn();
+        }
+    }
+}
+
+fn animate_check_indicators(
+    time: Res<Time>,
+    mut indicators: Query<&mut Transform, With<CheckIndicator>>,
+) {
+    let pulse = 1.0 + (time.elapsed_secs() * 6.0).sin() * 0.15;
+    for mut transform in &mut indicators {
+        transform.scale = Vec3::splat(pulse);
+        transform.rotate_y(time.delta_secs());
+    }
+}
+
+fn flash_illegal_moves(
+    mut illegal: EventReader<IllegalMove>,
+    axes: Res<RenderedAxes>,
+    assets: Res<IndicatorAssets>,
+    mut commands: Commands,
+) {
+    for illegal in illegal.read() {
+        info!("Can't move to {:?}", illegal.to.0);
+        let scale = illegal.to.render_scale(&axes);
+        commands.spawn((
+            Name::new("Illegal Move"),
+            IllegalMoveFlash { elapsed: 0., scale },
+            OnCell(illegal.to.clone()),
+            Mesh3d(assets.cell_mesh.clone()),
+            MeshMaterial3d(assets.illegal_material.clone()),
+            Transform::from_translation(illegal.to.to_translation(&axes) + Vec3::Y * 0.01)
+                .with_scale(Vec3::splat(scale)),
+            Pickable::IGNORE,
+        ));
+    }
+}
+
+/// Shrink the flash away over half a second
+fn fade_illegal_move_flashes(
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &mut Transform, &mut IllegalMoveFlash)>,
+    mut commands: Commands,
+) {
+    const DURATION: f32 = 0.5;
+    for (entity, mut transform, mut flash) in &mut flashes {
+        flash.elapsed += time.delta_secs();
+        if flash.elapsed >= DURATION {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.scale = Vec3::splat(flash.scale * (1. - flash.elapsed / DURATION));
+    }
+}
+
+/// Pieces can move a long way in world space when they jump between boards, so draw where they went
+fn draw_move_trail(last_move: Res<LastMove>, axes: Res<RenderedAxes>, mut gizmos: Gizmos) {
+    if !last_move.crosses_dimensions() {
+        return;
+    }
+    let Some((from, to)) = &last_move.0 else {
+        return;
+    };
+    let start = from.to_translation(&axes) + Vec3::Y * 0.5;
+    let end = to.to_translation(&axes) + Vec3::Y * 0.5;
+    gizmos
+        .arrow(start, end, Color::linear_rgb(1.0, 0.8, 0.0))
+        .with_tip_length(0.5);
+}
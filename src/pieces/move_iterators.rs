@@ -1,4 +1,4 @@
-use crate::board::Position;
+use crate::board::{MAX_REACH, Position};
 
 mod bishop;
 mod knight;
@@ -6,7 +6,7 @@ pub struct BishopMoveIterator {
 }
 
 impl Iterator for BishopMoveIterator {
-    type Item = DiagonalIter<7>;
+    type Item = DiagonalIter<{ MAX_REACH as u8 }>;
     fn next(&mut self) -> Option<Self::Item> {
         if self.move_set >= 1 << self.len {
             return None;
@@ -0,0 +1,226 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedFolder, io::Reader},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::board::BoardTheme;
+use crate::pieces::{ChessPiece, PieceAssets};
+
+/// Every `*.pieces.ron` manifest in `assets/pieces` is a piece set that can be switched to with M.
+/// The primitive shapes are always there as the first set and fill in any piece a set doesn't have.
+pub struct PieceSetPlugin;
+
+impl Plugin for PieceSetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<PieceSet>()
+            .init_asset_loader::<PieceSetLoader>()
+            .init_resource::<PieceSets>()
+            .add_systems(Update, (cycle_piece_set, apply_piece_set).chain());
+    }
+}
+
+/// A loaded piece set manifest
+#[derive(Asset, TypePath, Debug)]
+pub struct PieceSet {
+    pub name: String,
+    pub meshes: HashMap<ChessPiece, Handle<Mesh>>,
+    pub white: Color,
+    pub black: Color,
+    /// light and dark cell colours to go with the pieces
+    pub board: Option<(Color, Color)>,
+}
+
+type Rgb = (f32, f32, f32);
+
+/// What a `*.pieces.ron` file looks like
+#[derive(Deserialize)]
+struct PieceSetManifest {
+    name: String,
+    white: Rgb,
+    black: Rgb,
+    #[serde(default)]
+    board: Option<(Rgb, Rgb)>,
+    /// glTF file for each piece, a path without a label uses the first primitive of the first mesh
+    #[serde(default)]
+    pieces: HashMap<ChessPiece, String>,
+}
+
+#[derive(Default)]
+struct PieceSetLoader;
+
+impl AssetLoader for PieceSetLoader {
+    type Asset = PieceSet;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<PieceSet, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let manifest = ron::de::from_bytes::<PieceSetManifest>(&bytes)?;
+        let rgb = |(r, g, b): Rgb| Color::srgb(r, g, b);
+        let meshes = manifest
+            .pieces
+            .into_iter()
+            .map(|(piece, path)| {
+                let path = if path.contains('#') {
+                    path
+                } else {
+                    format!("{path}#Mesh0/Primitive0")
+                };
+                (piece, load_context.load(path))
+            })
+            .collect();
+        Ok(PieceSet {
+            name: manifest.name,
+            meshes,
+            white: rgb(manifest.white),
+            black: rgb(manifest.black),
+            board: manifest.board.map(|(light, dark)| (rgb(light), rgb(dark))),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pieces.ron"]
+    }
+}
+
+/// The piece sets found in `assets/pieces` and the one in use, `None` is the primitive shapes
+#[derive(Resource)]
+pub struct PieceSets {
+    folder: Handle<LoadedFolder>,
+    pub current: Option<Handle<PieceSet>>,
+}
+
+impl FromWorld for PieceSets {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            folder: world.resource::<AssetServer>().load_folder("pieces"),
+            current: None,
+        }
+    }
+}
+
+impl PieceSets {
+    fn available(&self, folders: &Assets<LoadedFolder>) -> Vec<Handle<PieceSet>> {
+        folders
+            .get(&self.folder)
+            .map(|folder| {
+                folder
+                    .handles
+                    .iter()
+                    .filter_map(|handle| handle.clone().try_typed::<PieceSet>().ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl PieceAssets {
+    /// The mesh for `piece` in `set`, or the primitive if the set doesn't have one or it failed to load
+    pub(super) fn mesh(
+        &self,
+        piece: ChessPiece,
+        set: Option<&PieceSet>,
+        meshes: &Assets<Mesh>,
+    ) -> Handle<Mesh> {
+        set.and_then(|set| set.meshes.get(&piece))
+            .filter(|mesh| meshes.contains(*mesh))
+            .unwrap_or(&self.meshes[&piece])
+            .clone()
+    }
+}
+
+/// M switches to the next piece set, going back to the primitives after the last one
+fn cycle_piece_set(
+    input: Res<ButtonInput<KeyCode>>,
+    mut sets: ResMut<PieceSets>,
+    folders: Res<Assets<LoadedFolder>>,
+) {
+    if !input.just_pressed(KeyCode::KeyM) {
+        return;
+    }
+    let available = sets.available(&folders);
+    let next = match &sets.current {
+        None => available.first(),
+        Some(current) => available
+            .iter()
+            .position(|set| set == current)
+            .and_then(|i| available.get(i + 1)),
+    };
+    sets.current = next.cloned();
+}
+
+/// The name the board theme goes by while a piece set's board colours are shown
+const PIECE_SET_THEME: &str = "Piece Set";
+
+/// Swap every piece over to the current set once it has finished loading.
+/// A set with board colours replaces the theme's, the theme is put back for a set without them.
+fn apply_piece_set(
+    sets: Res<PieceSets>,
+    mut loaded: EventReader<AssetEvent<PieceSet>>,
+    piece_sets: Res<Assets<PieceSet>>,
+    meshes: Res<Assets<Mesh>>,
+    assets: Res<PieceAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut theme: ResMut<BoardTheme>,
+    pieces: Query<(Entity, &ChessPiece)>,
+    mut saved_theme: Local<Option<BoardTheme>>,
+    mut commands: Commands,
+) {
+    let current_loaded = loaded.read().any(|event| match (event, &sets.current) {
+        (AssetEvent::LoadedWithDependencies { id }, Some(current)) => *id == current.id(),
+        _ => false,
+    });
+    if !sets.is_changed() && !current_loaded {
+        return;
+    }
+    let set = sets.current.as_ref().and_then(|set| piece_sets.get(set));
+    if sets.current.is_some() && set.is_none() {
+        // still loading, this runs again once it has
+        return;
+    }
+    info!(
+        "Piece set: {}",
+        set.map_or("Primitives", |set| set.name.as_str())
+    );
+    let (white, black) = set.map_or((Color::WHITE, Color::BLACK), |set| (set.white, set.black));
+    if let Some(material) = materials.get_mut(&assets.white_material) {
+        material.base_color = white;
+    }
+    if let Some(material) = materials.get_mut(&assets.black_material) {
+        material.base_color = black;
+    }
+    match set.and_then(|set| set.board) {
+        Some((light, dark)) => {
+            if theme.name != PIECE_SET_THEME {
+                *saved_theme = Some(theme.clone());
+            }
+            theme.name = PIECE_SET_THEME;
+            theme.light = light;
+            theme.dark = dark;
+        }
+        None => {
+            // a theme picked with B since the set was applied is kept
+            if let Some(saved) = saved_theme.take()
+                && theme.name == PIECE_SET_THEME
+            {
+                *theme = BoardTheme {
+                    tint_slices: theme.tint_slices,
+                    ..saved
+                };
+            }
+        }
+    }
+    for (entity, piece) in &pieces {
+        commands
+            .entity(entity)
+            .insert(Mesh3d(assets.mesh(*piece, set, &meshes)));
+    }
+}
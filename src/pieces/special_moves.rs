@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use crate::board::{BoardState, Position};
+use crate::pieces::{ChessPiece, Team, attack_map};
+
+/// Set on a piece once it has moved, castling needs both the king and rook unmoved
+#[derive(Component)]
+pub struct HasMoved;
+
+/// What `piece` turns into when it moves to `to` on a board of `size`, pawns on their last rank always become queens
+pub fn promotion(piece: ChessPiece, team: Team, to: &Position, size: i8) -> Option<ChessPiece> {
+    (piece == ChessPiece::Pawn && to[0] == team.promotion_rank(size)).then_some(ChessPiece::Queen)
+}
+
+/// The king moves two cells along axis 1 towards an unmoved rook on the same line, and the rook
+/// lands on the cell the king crossed. Returns the king's destination and the rook's move.
+pub fn castling_moves(
+    position: &Position,
+    team: Team,
+    board: &BoardState,
+    info: &Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+) -> Vec<(Position, Position, Position)> {
+    let lookup = |entity: Entity| info.get(entity).ok().map(|(p, t, _)| (*p, *t));
+    let knights = attack_map::knights(board, team.opposite(), lookup);
+    let attacked = |cell: &Position| {
+        attack_map::is_cell_attacked(board, cell, team.opposite(), &knights, lookup)
+    };
+    if position.len() < 2 || attacked(position) {
+        return Vec::new();
+    }
+    let mut moves = Vec::new();
+    for up in [true, false] {
+        let step = |p: Position| if up { p.inc(1) } else { p.dec(1) };
+        let mut next = step(position.clone());
+        let rook = loop {
+            if !board.contains(&next) {
+                break None;
+            }
+            if let Some(entity) = board.get(&next) {
+                break Some(entity);
+            }
+            next = step(next);
+        };
+        let Some(Ok((ChessPiece::Rook, rook_team, false))) = rook.map(|rook| info.get(rook)) else {
+            continue;
+        };
+        let crossed = step(position.clone());
+        let to = step(crossed.clone());
+        if *rook_team != team || to == next || attacked(&crossed) || attacked(&to) {
+            continue;
+        }
+        moves.push((to, next, crossed));
+    }
+    moves
+}
+
+/// Mark the piece that moved, promote it and move the castling rook, after the piece itself has been moved
+pub fn apply(
+    entity: Entity,
+    promotion: Option<ChessPiece>,
+    castle: Option<&(Position, Position)>,
+    board: &mut BoardState,
+    commands: &mut Commands,
+) {
+    commands.entity(entity).insert(HasMoved);
+    if let Some(promotion) = promotion {
+        commands.entity(entity).insert(promotion);
+    }
+    if let Some((rook_from, rook_to)) = castle
+        && let Some(rook) = board.get(rook_from)
+    {
+        board.move_piece(rook_from, rook_to);
+        commands.entity(rook).insert((rook_to.clone(), HasMoved));
+    }
+}
@@ -0,0 +1,485 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use bevy::prelude::*;
+
+use crate::board::{BoardState, Position};
+use crate::game::{InGame, NewGameSet};
+use crate::pieces::{ChessPiece, MoveMade, Team};
+
+pub struct RulesPlugin;
+
+impl Plugin for RulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DrawRules>()
+            .init_resource::<MoveHistory>()
+            .add_systems(
+                OnEnter(InGame),
+                record_initial_position.in_set(NewGameSet::Record),
+            )
+            .add_systems(Update, check_draw_rules.run_if(in_state(InGame)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DrawReason {
+    FiftyMove,
+    Repetition,
+    InsufficientMaterial,
+    /// the side to move has no move and isn't in check
+    Stalemate,
+}
+
+/// Inserted once the game has ended, no more moves can be made while this exists
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum GameOutcome {
+    Draw(DrawReason),
+    /// this side ran out of time and lost
+    Flagged(Team),
+    /// this side has no move and its king is attacked
+    Checkmate(Team),
+    /// this side's engine sent a move that isn't legal here or stopped, and lost
+    Forfeit(Team),
+}
+
+/// Draw conditions for the current variant, `None` disables a rule
+#[derive(Resource, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DrawRules {
+    /// number of half moves without a pawn move or capture before the game is drawn
+    pub halfmove_limit: Option<u32>,
+    /// number of times the same position must be seen before the game is drawn
+    pub repetition_limit: Option<usize>,
+    pub insufficient_material: Option<InsufficientMaterial>,
+}
+
+impl Default for DrawRules {
+    fn default() -> Self {
+        Self {
+            halfmove_limit: Some(100),
+            repetition_limit: Some(3),
+            insufficient_material: Some(InsufficientMaterial::default()),
+        }
+    }
+}
+
+/// A side is treated as unable to mate when it has no pawns, rooks or queens
+/// and no more then `minor_piece_limit` bishops and knights.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct InsufficientMaterial {
+    /// 1 like a 2D board, variants where a king has more room to escape can raise it
+    pub minor_piece_limit: usize,
+    /// bishops move every axis at once so can only ever reach cells with the same (or inverted) parity on every axis,
+    /// when set bishops that share a class only count once
+    pub merge_bishop_classes: bool,
+}
+
+impl Default for InsufficientMaterial {
+    fn default() -> Self {
+        Self {
+            minor_piece_limit: 1,
+            merge_bishop_classes: false,
+        }
+    }
+}
+
+impl InsufficientMaterial {
+    fn is_insufficient(&self, material: &[(ChessPiece, &Position)]) -> bool {
+        let mut knights = 0;
+        let mut bishop_classes = Vec::new();
+        let mut bishops = 0;
+        for (piece, position) in material {
+            match piece {
+                ChessPiece::King => {}
+                ChessPiece::Knight => knights += 1,
+                ChessPiece::Bishop => {
+                    bishops += 1;
+                    let class = bishop_class(position);
+                    if !bishop_classes.contains(&class) {
+                        bishop_classes.push(class);
+                    }
+                }
+                ChessPiece::Pawn | ChessPiece::Rook | ChessPiece::Queen => return false,
+            }
+        }
+        let bishops = if self.merge_bishop_classes {
+            bishop_classes.len()
+        } else {
+            bishops
+        };
+        knights + bishops <= self.minor_piece_limit
+    }
+}
+
+/// The parity of each axis, flipped so the first axis is always even
+fn bishop_class(position: &Position) -> u64 {
+    let mut class = 0;
+    for (i, v) in position.iter().enumerate() {
+        if v.rem_euclid(2) == 1 {
+            class |= 1 << i;
+        }
+    }
+    if class & 1 == 1 {
+        class = !class & ((1 << position.len()) - 1);
+    }
+    class
+}
+
+/// Every move made this game along with what is needed to detect draws
+#[derive(Resource, Default)]
+pub struct MoveHistory {
+    pub moves: Vec<MoveMade>,
+    halfmove_clock: u32,
+    positions: Vec<u64>,
+}
+
+impl MoveHistory {
+    /// No moves yet, from the position with `key`
+    pub fn starting_from(key: u64) -> Self {
+        Self {
+            positions: vec![key],
+            ..Default::default()
+        }
+    }
+
+    /// Half moves since the last pawn move or capture
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// How many times the current position has been seen
+    pub fn repetitions(&self) -> usize {
+        let Some(current) = self.positions.last() else {
+            return 0;
+        };
+        // positions before the last pawn move or capture can never be repeated
+        self.positions
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize + 1)
+            .filter(|&key| key == current)
+            .count()
+    }
+
+    fn push(&mut self, made: MoveMade) {
+        if made.piece == ChessPiece::Pawn || made.captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        self.positions.push(made.key);
+        self.moves.push(made);
+    }
+}
+
+/// Hash of every piece on the board and the side to move, order independent
+pub fn position_key(board: &BoardState, pieces: &Query<(&ChessPiece, &Team)>, turn: Team) -> u64 {
+    position_key_with(board, turn, |entity| {
+        pieces.get(entity).ok().map(|(piece, team)| (*piece, *team))
+    })
+}
+
+/// `position_key` with each piece looked up by `piece_of`, for a board whose commands haven't been applied yet
+pub fn position_key_with(
+    board: &BoardState,
+    turn: Team,
+    piece_of: impl Fn(Entity) -> Option<(ChessPiece, Team)>,
+) -> u64 {
+    let mut key = hash_one(turn);
+    for (position, entity) in board.iter() {
+        if let Some((piece, team)) = piece_of(entity) {
+            key ^= hash_one((position, &piece, &team));
+        }
+    }
+    key
+}
+
+fn hash_one(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn record_initial_position(
+    mut history: ResMut<MoveHistory>,
+    board: Res<BoardState>,
+    pieces: Query<(&ChessPiece, &Team)>,
+    turn: Res<Team>,
+) {
+    *history = MoveHistory::starting_from(position_key(&board, &pieces, *turn));
+}
+
+pub fn check_draw_rules(
+    mut moves: EventReader<MoveMade>,
+    mut history: ResMut<MoveHistory>,
+    rules: Res<DrawRules>,
+    board: Res<BoardState>,
+    pieces: Query<(&ChessPiece, &Team)>,
+    outcome: Option<Res<GameOutcome>>,
+    mut commands: Commands,
+) {
+    if moves.is_empty() {
+        return;
+    }
+    for made in moves.read() {
+        history.push(made.clone());
+    }
+    if outcome.is_some() {
+        return;
+    }
+    let reason = if rules
+        .halfmove_limit
+        .is_some_and(|limit| history.halfmove_clock() >= limit)
+    {
+        Some(DrawReason::FiftyMove)
+    } else if rules
+        .repetition_limit
+        .is_some_and(|limit| history.repetitions() >= limit)
+    {
+        Some(DrawReason::Repetition)
+    } else if let Some(insufficient) = &rules.insufficient_material {
+        let mut white = Vec::new();
+        let mut black = Vec::new();
+        for (position, entity) in board.iter() {
+            let Ok((piece, team)) = pieces.get(entity) else {
+                continue;
+            };
+            match team {
+                Team::White => white.push((*piece, position)),
+                Team::Black => black.push((*piece, position)),
+            }
+        }
+        if insufficient.is_insufficient(&white) && insufficient.is_insufficient(&black) {
+            Some(DrawReason::InsufficientMaterial)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+    if let Some(reason) = reason {
+        info!("Game drawn: {reason:?}");
+        commands.insert_resource(GameOutcome::Draw(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::pieces::{
+        HasMoved, MoveKind, StartingSetup, checked_move, classified_moves, no_move_outcome,
+        play_move, starting_pieces,
+    };
+
+    fn made(piece: ChessPiece, captured: Option<ChessPiece>, key: u64) -> MoveMade {
+        MoveMade {
+            entity: Entity::PLACEHOLDER,
+            piece,
+            team: Team::White,
+            from: Position(vec![0, 0]),
+            to: Position(vec![1, 0]),
+            captured,
+            kind: MoveKind::default(),
+            key,
+        }
+    }
+
+    fn key(world: &mut World, turn: Team) -> u64 {
+        world
+            .run_system_once(
+                move |board: Res<BoardState>, pieces: Query<(&ChessPiece, &Team)>| {
+                    position_key(&board, &pieces, turn)
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn halfmove_clock_resets_on_pawn_moves_and_captures() {
+        let mut history = MoveHistory::starting_from(0);
+        history.push(made(ChessPiece::Knight, None, 1));
+        history.push(made(ChessPiece::Rook, None, 2));
+        assert_eq!(history.halfmove_clock(), 2);
+        history.push(made(ChessPiece::Pawn, None, 3));
+        assert_eq!(history.halfmove_clock(), 0);
+        history.push(made(ChessPiece::Queen, None, 4));
+        assert_eq!(history.halfmove_clock(), 1);
+        history.push(made(ChessPiece::Knight, Some(ChessPiece::Bishop), 5));
+        assert_eq!(history.halfmove_clock(), 0);
+    }
+
+    #[test]
+    fn repetitions_only_count_since_the_last_pawn_move_or_capture() {
+        let mut history = MoveHistory::starting_from(1);
+        for key in [2, 1, 2, 1] {
+            history.push(made(ChessPiece::Knight, None, key));
+        }
+        assert_eq!(history.repetitions(), 3);
+        history.push(made(ChessPiece::Pawn, None, 1));
+        assert_eq!(history.repetitions(), 1);
+        history.push(made(ChessPiece::Knight, None, 2));
+        history.push(made(ChessPiece::Knight, None, 1));
+        assert_eq!(history.repetitions(), 2);
+    }
+
+    #[test]
+    fn position_key_is_the_pieces_and_side_to_move() {
+        let mut world = World::new();
+        world.init_resource::<BoardState>();
+        world.spawn((Position(vec![0, 4, 0]), ChessPiece::King, Team::White));
+        world.spawn((Position(vec![7, 4, 0]), ChessPiece::King, Team::Black));
+        let knight = world
+            .spawn((Position(vec![0, 1, 0]), ChessPiece::Knight, Team::White))
+            .id();
+        world.flush();
+        let start = key(&mut world, Team::White);
+        assert_eq!(key(&mut world, Team::White), start);
+        assert_ne!(key(&mut world, Team::Black), start);
+
+        // the same pieces placed in another order are the same position
+        let mut other = World::new();
+        other.init_resource::<BoardState>();
+        other.spawn((Position(vec![0, 1, 0]), ChessPiece::Knight, Team::White));
+        other.spawn((Position(vec![7, 4, 0]), ChessPiece::King, Team::Black));
+        other.spawn((Position(vec![0, 4, 0]), ChessPiece::King, Team::White));
+        other.flush();
+        assert_eq!(key(&mut other, Team::White), start);
+
+        let moved = |world: &mut World, from: Vec<i8>, to: Vec<i8>| {
+            world
+                .resource_mut::<BoardState>()
+                .move_piece(&Position(from), &Position(to));
+            key(world, Team::White)
+        };
+        assert_ne!(moved(&mut world, vec![0, 1, 0], vec![2, 2, 0]), start);
+        // going back is a repetition
+        assert_eq!(moved(&mut world, vec![2, 2, 0], vec![0, 1, 0]), start);
+        // a different piece on the same cell isn't
+        world.entity_mut(knight).insert(ChessPiece::Bishop);
+        world.flush();
+        assert_ne!(key(&mut world, Team::White), start);
+    }
+
+    /// Play `moves` in one go like a catch up does, the keys the moves were given
+    fn play_at_once(world: &mut World, moves: &[(&str, &str)]) -> Vec<u64> {
+        let moves = moves
+            .iter()
+            .map(|(from, to)| (from.parse().unwrap(), to.parse().unwrap()))
+            .collect::<Vec<(Position, Position)>>();
+        world
+            .run_system_once(
+                move |mut board: ResMut<BoardState>,
+                      mut positions: Query<&mut Position, With<ChessPiece>>,
+                      info: Query<(&ChessPiece, &Team, Has<HasMoved>)>,
+                      pieces: Query<(&ChessPiece, &Team)>,
+                      mut commands: Commands| {
+                    let mut turn = Team::White;
+                    let mut keys = Vec::new();
+                    for (from, to) in &moves {
+                        let (entity, piece, kind) =
+                            checked_move(from, to, turn, &board, &info).unwrap();
+                        let mut position = positions.get_mut(entity).unwrap();
+                        let made = play_move(
+                            (entity, piece, turn),
+                            &mut position,
+                            to,
+                            &kind,
+                            &mut board,
+                            &pieces,
+                            &mut commands,
+                        );
+                        keys.push(made.key);
+                        turn = turn.opposite();
+                    }
+                    keys
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn moves_played_together_are_each_keyed() {
+        let mut world = World::new();
+        world.init_resource::<BoardState>();
+        for (position, piece, team) in starting_pieces(2, 8, StartingSetup::default()) {
+            world.spawn((position, piece, team));
+        }
+        world.flush();
+        let keys = play_at_once(&mut world, &[("g1", "f3"), ("g8", "f6"), ("f3", "g1")]);
+        assert_ne!(keys[0], keys[1]);
+        assert_ne!(keys[1], keys[2]);
+        assert_eq!(keys[2], key(&mut world, Team::Black));
+
+        // the promoted piece counts before its command is applied
+        let mut world = World::new();
+        world.init_resource::<BoardState>();
+        world.spawn((Position(vec![0, 4]), ChessPiece::King, Team::White));
+        world.spawn((Position(vec![5, 7]), ChessPiece::King, Team::Black));
+        world.spawn((Position(vec![6, 0]), ChessPiece::Pawn, Team::White));
+        world.flush();
+        let keys = play_at_once(&mut world, &[("a7", "a8")]);
+        assert_eq!(keys[0], key(&mut world, Team::Black));
+    }
+
+    #[test]
+    fn only_moves_that_keep_the_king_safe_count() {
+        let mut world = World::new();
+        world.init_resource::<BoardState>();
+        world.spawn((Position(vec![0, 0]), ChessPiece::King, Team::White));
+        world.spawn((Position(vec![0, 3]), ChessPiece::Rook, Team::White));
+        world.spawn((Position(vec![0, 7]), ChessPiece::Rook, Team::Black));
+        world.spawn((Position(vec![7, 7]), ChessPiece::King, Team::Black));
+        world.flush();
+        let moves = |world: &mut World, from: Vec<i8>| {
+            world
+                .run_system_once(
+                    move |board: Res<BoardState>,
+                          info: Query<(&ChessPiece, &Team, Has<HasMoved>)>| {
+                        let from = Position(from.clone());
+                        let (piece, ..) = info.get(board.get(&from).unwrap()).unwrap();
+                        classified_moves(&from, *piece, Team::White, true, &board, &info)
+                            .into_iter()
+                            .map(|(to, _)| to)
+                            .collect::<Vec<_>>()
+                    },
+                )
+                .unwrap()
+        };
+        // the rook is pinned to the king's rank
+        let pinned = moves(&mut world, vec![0, 3]);
+        assert!(!pinned.is_empty());
+        assert!(pinned.iter().all(|to| to[0] == 0));
+        assert!(pinned.contains(&Position(vec![0, 7])));
+    }
+
+    #[test]
+    fn no_legal_move_is_checkmate_or_stalemate() {
+        let outcome = |black: Vec<(Vec<i8>, ChessPiece)>| {
+            let mut world = World::new();
+            world.init_resource::<BoardState>();
+            world.spawn((Position(vec![0, 0]), ChessPiece::King, Team::White));
+            world.spawn((Position(vec![7, 7]), ChessPiece::King, Team::Black));
+            for (position, piece) in black {
+                world.spawn((Position(position), piece, Team::Black));
+            }
+            world.flush();
+            world
+                .run_system_once(
+                    |board: Res<BoardState>, info: Query<(&ChessPiece, &Team, Has<HasMoved>)>| {
+                        no_move_outcome(Team::White, &board, &info)
+                    },
+                )
+                .unwrap()
+        };
+        let queen = (vec![2, 1], ChessPiece::Queen);
+        assert_eq!(outcome(Vec::new()), None);
+        assert_eq!(
+            outcome(vec![queen.clone()]),
+            Some(GameOutcome::Draw(DrawReason::Stalemate))
+        );
+        assert_eq!(
+            outcome(vec![queen, (vec![0, 7], ChessPiece::Rook)]),
+            Some(GameOutcome::Checkmate(Team::White))
+        );
+    }
+}